@@ -0,0 +1,163 @@
+use std::{fmt, str::FromStr};
+
+use photon_rs::PhotonImage;
+
+/// An opaque RGB colour, written `#rrggbb` (the `#` is optional) on the command line and in the configuration files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 3]);
+
+impl Color {
+    pub const WHITE: Color = Color([255, 255, 255]);
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |index: usize| {
+            hex.get(index..index + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+        };
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(red), Some(green), Some(blue)) => Ok(Color([red, green, blue])),
+            _ => Err(format!("expected a colour like #rrggbb, got {}", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [red, green, blue] = self.0;
+        write!(f, "#{:02x}{:02x}{:02x}", red, green, blue)
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+/// How the background of the linearts is removed, they don't depend on the blur radius or the darken
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct AlphaParameters {
+    /// The colour of the background in the linearts made by the methods, it becomes transparent
+    pub background: Color,
+    /// The pixels whose channels are all closer than this to the background become fully transparent
+    pub transparency_threshold: u8,
+    /// The pixels with a channel further than this from the background stay fully opaque
+    pub opacity_threshold: u8,
+    /// When set, the linearts are drawn on this colour instead of a transparent background
+    pub paper: Option<Color>,
+}
+
+impl Default for AlphaParameters {
+    fn default() -> Self {
+        AlphaParameters {
+            background: Color::WHITE,
+            transparency_threshold: 0,
+            opacity_threshold: 255,
+            paper: None,
+        }
+    }
+}
+
+/// Removes the background colour like the colour to alpha of GIMP
+/// The alpha of a pixel comes from its channel that is the furthest from the background, scaled between the thresholds,
+/// then its colour is un-premultiplied: the ink colour is the one that gives back the pixel when drawn with this alpha
+/// over the background, so that black lines anti-aliased on white become black with a partial alpha
+fn color_to_alpha(pixel: [u8; 4], parameters: &AlphaParameters) -> [u8; 4] {
+    let background = parameters.background.0;
+    let transparency_threshold = parameters.transparency_threshold as f32;
+    let opacity_threshold = parameters.opacity_threshold as f32;
+    let mut alpha = 0_f32;
+    for channel in 0..3 {
+        let distance = (pixel[channel] as f32 - background[channel] as f32).abs();
+        let channel_alpha = if distance <= transparency_threshold {
+            0_f32
+        } else if distance >= opacity_threshold {
+            1_f32
+        } else {
+            (distance - transparency_threshold) / (opacity_threshold - transparency_threshold)
+        };
+        alpha = alpha.max(channel_alpha);
+    }
+    if alpha == 0_f32 {
+        return [background[0], background[1], background[2], 0];
+    }
+    let mut ink = [0_u8; 4];
+    for channel in 0..3 {
+        let value = background[channel] as f32
+            + (pixel[channel] as f32 - background[channel] as f32) / alpha;
+        ink[channel] = value.round().clamp(0_f32, 255_f32) as u8;
+    }
+    ink[3] = (alpha * pixel[3] as f32).round() as u8;
+    ink
+}
+
+/// Makes the background of the lineart transparent, see [`AlphaParameters`]
+pub fn image_color_to_alpha(image: &PhotonImage, parameters: &AlphaParameters) -> PhotonImage {
+    let mut raw_pixels = image.get_raw_pixels();
+    for pixel in raw_pixels.chunks_exact_mut(4) {
+        let alpha_pixel = color_to_alpha([pixel[0], pixel[1], pixel[2], pixel[3]], parameters);
+        pixel.copy_from_slice(&alpha_pixel);
+    }
+    PhotonImage::new(raw_pixels, image.get_width(), image.get_height())
+}
+
+/// Draws the lineart on an opaque paper colour
+pub fn image_on_paper(image: &PhotonImage, paper: Color) -> PhotonImage {
+    let mut raw_pixels = image.get_raw_pixels();
+    for pixel in raw_pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3] as f32 / 255_f32;
+        for (channel, paper_channel) in pixel[0..3].iter_mut().zip(paper.0) {
+            let value = *channel as f32 * alpha + paper_channel as f32 * (1_f32 - alpha);
+            *channel = value.round() as u8;
+        }
+        pixel[3] = 255;
+    }
+    PhotonImage::new(raw_pixels, image.get_width(), image.get_height())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blue_channel_counts_in_the_distance() {
+        // only the blue channel differs from the white background, the distance used to ignore it and made it transparent
+        let yellow = color_to_alpha([255, 255, 0, 255], &AlphaParameters::default());
+        assert_eq!(yellow, [255, 255, 0, 255]);
+        let light_yellow = color_to_alpha([255, 255, 128, 255], &AlphaParameters::default());
+        assert_eq!(light_yellow, [255, 255, 0, 127]);
+    }
+
+    #[test]
+    fn grey_becomes_black_with_a_partial_alpha() {
+        let parameters = AlphaParameters::default();
+        assert_eq!(color_to_alpha([255, 255, 255, 255], &parameters)[3], 0);
+        assert_eq!(color_to_alpha([0, 0, 0, 255], &parameters), [0, 0, 0, 255]);
+        assert_eq!(
+            color_to_alpha([128, 128, 128, 255], &parameters),
+            [0, 0, 0, 127]
+        );
+    }
+
+    #[test]
+    fn colour_is_parsed_and_written_as_hex() {
+        assert_eq!("#ff8000".parse::<Color>(), Ok(Color([255, 128, 0])));
+        assert_eq!("ff8000".parse::<Color>(), Ok(Color([255, 128, 0])));
+        assert!("#ff80".parse::<Color>().is_err());
+        assert_eq!(Color([255, 128, 0]).to_string(), "#ff8000");
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    image_generation::build_image_output_path, manifest::MANIFEST_FILE_NAME, params::LineartParams,
+    summary, sweep::Variant,
+};
+
+/// The file of the output directory of an image that records how its outputs were made
+const CACHE_FILE_NAME: &str = ".lineart_cache.json";
+
+/// What the outputs of an image were made from, to only regenerate the ones that changed on the next run
+/// The values are hashes, the cache of another input image is not used at all
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Cache {
+    input: String,
+    /// The hash of the parameters of each generated image, by name
+    variants: BTreeMap<String, String>,
+    /// The hash of the parameters of the summaries, when they were generated
+    summary: Option<String>,
+}
+
+impl Cache {
+    /// An empty cache for the input
+    pub(crate) fn new(input: &[u8]) -> Self {
+        Cache {
+            input: hash(input),
+            ..Cache::default()
+        }
+    }
+
+    /// The cache of the directory, or an empty one when there is none, when it cannot be read or when it is for another input
+    pub(crate) fn read(output_dir_for_images: &Path, input: &[u8]) -> Self {
+        let cache = fs::read_to_string(cache_path(output_dir_for_images))
+            .ok()
+            .and_then(|text| serde_json::from_str::<Cache>(&text).ok());
+        match cache {
+            Some(cache) if cache.input == hash(input) => cache,
+            Some(_) => {
+                debug!(
+                    "{:?}: the input changed, every image is generated again",
+                    output_dir_for_images
+                );
+                Cache::new(input)
+            }
+            None => Cache::new(input),
+        }
+    }
+
+    pub(crate) fn write(&self, output_dir_for_images: &Path) -> Result<()> {
+        fs::write(
+            cache_path(output_dir_for_images),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Whether the image of the variant and its exported files exist and were made with the same parameters and the same metadata
+    pub(crate) fn is_current(
+        &self,
+        variant: &Variant,
+        base_image_path: &Path,
+        output_dir_for_images: &Path,
+    ) -> bool {
+        self.variants.get(&variant.name) == Some(&variant_hash(variant, base_image_path))
+            && build_image_output_path(output_dir_for_images, &variant.name).is_ok_and(|path| {
+                let path = Path::new(&path);
+                path.is_file()
+                    && variant
+                        .params
+                        .export_parameters
+                        .formats
+                        .iter()
+                        .all(|format| format.path(path).is_file())
+            })
+    }
+
+    pub(crate) fn set_current(&mut self, variant: &Variant, base_image_path: &Path) {
+        self.variants
+            .insert(variant.name.clone(), variant_hash(variant, base_image_path));
+    }
+
+    /// Whether the summaries and the manifest exist and the summaries were made with the same parameters,
+    /// they also have to be generated again when one of their images is
+    pub(crate) fn is_summary_current(
+        &self,
+        params: &LineartParams,
+        output_dir_for_images: &Path,
+    ) -> bool {
+        let summary_files = params
+            .sweep
+            .facets()
+            .iter()
+            .map(|facet| format!("{}.png", params.sweep.summary_name(facet)))
+            .chain([
+                String::from("summary.html"),
+                String::from(MANIFEST_FILE_NAME),
+            ])
+            .map(|name| output_dir_for_images.join(name))
+            .collect::<Vec<PathBuf>>();
+        self.summary.as_ref() == Some(&summary_hash(params))
+            && summary_files.iter().all(|path| path.is_file())
+    }
+
+    pub(crate) fn set_summary_current(&mut self, params: &LineartParams) {
+        self.summary = Some(summary_hash(params));
+    }
+}
+
+fn cache_path(output_dir_for_images: &Path) -> PathBuf {
+    output_dir_for_images.join(CACHE_FILE_NAME)
+}
+
+/// The hash of everything that changes the image of a variant, the version of the crate included since the methods can change
+/// The path of the image and the command written in the metadata of the PNG are included too,
+/// so that `inspect` never shows the command of an older run with other arguments
+fn variant_hash(variant: &Variant, base_image_path: &Path) -> String {
+    let params = &variant.params;
+    let command = summary::variant_command(params, base_image_path, &variant.indices);
+    let prefilter = params
+        .prefilter
+        .map(|prefilter| (prefilter, variant.prefilter_strength));
+    let description = format!(
+        "{} {:?} {:?} {:?} {:?} {} {} {:?} {:?} {:?} {:?} {:?} {}",
+        env!("CARGO_PKG_VERSION"),
+        params.target_size,
+        params.method,
+        params.method_parameters,
+        prefilter,
+        variant.blur_radius,
+        variant.darken,
+        params.alpha,
+        params.despeckle,
+        params.ink,
+        params.export_parameters,
+        base_image_path,
+        command,
+    );
+    hash(description.as_bytes())
+}
+
+/// The hash of everything that changes the summaries apart from their images
+fn summary_hash(params: &LineartParams) -> String {
+    let description = format!(
+        "{} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        params.sweep,
+        params.summary
+    );
+    hash(description.as_bytes())
+}
+
+/// The 64 bits FNV-1a hash of the bytes, which stays the same between the runs and the versions of Rust
+pub(crate) fn hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{Export, ExportParameters};
+
+    fn only_variant(params: LineartParams) -> Variant {
+        params.variants().unwrap().remove(0)
+    }
+
+    fn params_exporting_svg() -> LineartParams {
+        LineartParams::builder()
+            .blur_number(1)
+            .darken_number(1)
+            .export_parameters(ExportParameters {
+                formats: vec![Export::Svg],
+                ..ExportParameters::default()
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn hash_is_fnv_1a() {
+        assert_eq!(hash(b""), "cbf29ce484222325");
+        assert_eq!(hash(b"a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn variant_is_current_when_its_files_exist_with_the_same_parameters() {
+        let directory =
+            std::env::temp_dir().join(format!("lineart_ify_cache_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let variant = only_variant(params_exporting_svg());
+        let png_path = PathBuf::from(build_image_output_path(&directory, &variant.name).unwrap());
+        let input_path = directory.join("input.png");
+        let mut cache = Cache::new(b"input");
+        cache.set_current(&variant, &input_path);
+        let missing_png = cache.is_current(&variant, &input_path, &directory);
+        fs::write(&png_path, b"").unwrap();
+        let missing_svg = cache.is_current(&variant, &input_path, &directory);
+        fs::write(Export::Svg.path(&png_path), b"").unwrap();
+        let complete = cache.is_current(&variant, &input_path, &directory);
+        cache.write(&directory).unwrap();
+        let read_again =
+            Cache::read(&directory, b"input").is_current(&variant, &input_path, &directory);
+        let other_input =
+            Cache::read(&directory, b"other input").is_current(&variant, &input_path, &directory);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(!missing_png);
+        assert!(!missing_svg);
+        assert!(complete);
+        assert!(read_again);
+        assert!(!other_input);
+    }
+
+    #[test]
+    fn changed_parameters_or_input_path_change_the_hash() {
+        let variant = only_variant(params_exporting_svg());
+        let mut params = params_exporting_svg();
+        params.export_parameters.tolerance = 2.0;
+        let changed = only_variant(params);
+        let input_path = Path::new("input.png");
+        assert_eq!(variant.name, changed.name);
+        assert_ne!(
+            variant_hash(&variant, input_path),
+            variant_hash(&changed, input_path)
+        );
+        assert_ne!(
+            variant_hash(&variant, input_path),
+            variant_hash(&variant, Path::new("other.png"))
+        );
+    }
+}
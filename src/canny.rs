@@ -0,0 +1,186 @@
+use anyhow::{bail, Result};
+use image::{GrayImage, ImageBuffer, Luma};
+
+use crate::gradient::{self, Gradient, Kernel};
+
+/// Value given to the pixels that are kept as edges, every other pixel is 0
+const EDGE: u8 = 255;
+
+/// Runs the Canny edge detector on an already blurred greyscale image
+/// The gradient is computed with `kernel`, then thinned to one pixel wide lines with a non-maximum suppression
+/// Finally, the hysteresis keeps the pixels above `high_threshold` and the pixels above `low_threshold` connected to them
+/// The thresholds are gradient magnitudes in grey levels per pixel, a sharp edge from black to white has a magnitude of 255
+/// The returned image has the edges in white (255) on a black (0) background
+pub(crate) fn canny_edges(
+    image: &GrayImage,
+    low_threshold: f32,
+    high_threshold: f32,
+    kernel: Kernel,
+) -> Result<GrayImage> {
+    check_thresholds(low_threshold, high_threshold)?;
+    let gradient = Gradient::new(&gradient::gray_to_float(image), kernel);
+
+    let magnitude: ImageBuffer<Luma<f32>, Vec<f32>> =
+        ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+            let gx = gradient.x.get_pixel(x, y).0[0];
+            let gy = gradient.y.get_pixel(x, y).0[0];
+            Luma([(gx * gx + gy * gy).sqrt() * 255_f32])
+        });
+
+    let thinned = non_maximum_suppression(&magnitude, |x, y| {
+        (
+            gradient.x.get_pixel(x, y).0[0],
+            gradient.y.get_pixel(x, y).0[0],
+        )
+    });
+    Ok(hysteresis(&thinned, low_threshold, high_threshold))
+}
+
+/// Checks the hysteresis thresholds, they must be positive with `low_threshold` not above `high_threshold`
+pub(crate) fn check_thresholds(low_threshold: f32, high_threshold: f32) -> Result<()> {
+    for threshold in [low_threshold, high_threshold] {
+        if !threshold.is_finite() || threshold < 0_f32 {
+            bail!(
+                "The Canny thresholds must be positive numbers, got {}",
+                threshold
+            );
+        }
+    }
+    if low_threshold > high_threshold {
+        bail!(
+            "The low Canny threshold ({}) must not be above the high threshold ({})",
+            low_threshold,
+            high_threshold
+        );
+    }
+    Ok(())
+}
+
+/// Only keeps the pixels whose magnitude is a local maximum along the gradient direction
+/// The direction is rounded to the closest of the 4 directions (horizontal, vertical and the two diagonals)
+fn non_maximum_suppression(
+    magnitude: &ImageBuffer<Luma<f32>, Vec<f32>>,
+    gradient: impl Fn(u32, u32) -> (f32, f32),
+) -> ImageBuffer<Luma<f32>, Vec<f32>> {
+    let width = magnitude.width();
+    let height = magnitude.height();
+    let mut thinned = ImageBuffer::new(width, height);
+    // the border pixels don't have all their neighbours, they are left at 0
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let current = magnitude.get_pixel(x, y).0[0];
+            if current == 0_f32 {
+                continue;
+            }
+            let (gx, gy) = gradient(x, y);
+            // angle in degrees between 0 and 180, the opposite direction gives the same neighbours
+            let mut angle = gy.atan2(gx).to_degrees();
+            if angle < 0_f32 {
+                angle += 180_f32;
+            }
+            let (dx, dy): (i64, i64) = match angle {
+                a if !(22.5..157.5).contains(&a) => (1, 0),
+                a if a < 67.5 => (1, 1),
+                a if a < 112.5 => (0, 1),
+                _ => (-1, 1),
+            };
+            let before = magnitude
+                .get_pixel((x as i64 - dx) as u32, (y as i64 - dy) as u32)
+                .0[0];
+            let after = magnitude
+                .get_pixel((x as i64 + dx) as u32, (y as i64 + dy) as u32)
+                .0[0];
+            if current >= before && current >= after {
+                thinned.put_pixel(x, y, Luma([current]));
+            }
+        }
+    }
+    thinned
+}
+
+/// Keeps the strong edges (above `high_threshold`) and the weak edges (above `low_threshold`) that are connected to a strong edge
+fn hysteresis(
+    thinned: &ImageBuffer<Luma<f32>, Vec<f32>>,
+    low_threshold: f32,
+    high_threshold: f32,
+) -> GrayImage {
+    let width = thinned.width();
+    let height = thinned.height();
+    let mut edges = GrayImage::new(width, height);
+    let mut to_visit = vec![];
+
+    for (x, y, pixel) in thinned.enumerate_pixels() {
+        if pixel.0[0] > 0_f32 && pixel.0[0] >= high_threshold && edges.get_pixel(x, y).0[0] == 0 {
+            edges.put_pixel(x, y, Luma([EDGE]));
+            to_visit.push((x, y));
+            // follow every weak edge connected to this strong edge
+            while let Some((current_x, current_y)) = to_visit.pop() {
+                for neighbour_y in current_y.saturating_sub(1)..=(current_y + 1).min(height - 1) {
+                    for neighbour_x in current_x.saturating_sub(1)..=(current_x + 1).min(width - 1)
+                    {
+                        let neighbour_magnitude = thinned.get_pixel(neighbour_x, neighbour_y).0[0];
+                        if edges.get_pixel(neighbour_x, neighbour_y).0[0] == 0
+                            && neighbour_magnitude > 0_f32
+                            && neighbour_magnitude >= low_threshold
+                        {
+                            edges.put_pixel(neighbour_x, neighbour_y, Luma([EDGE]));
+                            to_visit.push((neighbour_x, neighbour_y));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hysteresis_keeps_weak_edges_connected_to_strong_ones() {
+        // a strong edge followed by two weak ones, then a weak edge separated from them
+        let magnitudes = [0.0, 30.0, 10.0, 10.0, 0.0, 10.0, 0.0];
+        let thinned = ImageBuffer::from_fn(7, 3, |x, y| {
+            Luma([if y == 1 {
+                magnitudes[x as usize]
+            } else {
+                0_f32
+            }])
+        });
+        let edges = hysteresis(&thinned, 5.0, 20.0);
+        let row: Vec<u8> = (0..7).map(|x| edges.get_pixel(x, 1).0[0]).collect();
+        assert_eq!(row, [0, EDGE, EDGE, EDGE, 0, 0, 0]);
+        assert!(edges
+            .enumerate_pixels()
+            .all(|(_, y, pixel)| y == 1 || pixel.0[0] == 0));
+    }
+
+    #[test]
+    fn sharp_step_is_a_single_line() {
+        let image = GrayImage::from_fn(10, 10, |x, _| Luma([if x < 5 { 0 } else { 255 }]));
+        let edges = canny_edges(&image, 12.5, 25.0, Kernel::Sobel).unwrap();
+        let edge_columns: Vec<u32> = edges
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel.0[0] == EDGE)
+            .map(|(x, _, _)| x)
+            .collect();
+        assert!(!edge_columns.is_empty());
+        assert!(edge_columns.iter().all(|x| (4..=5).contains(x)));
+        // the magnitude of the step is 255, above any threshold up to it
+        assert!(canny_edges(&image, 255.0, 255.0, Kernel::Sobel)
+            .unwrap()
+            .pixels()
+            .any(|pixel| pixel.0[0] == EDGE));
+    }
+
+    #[test]
+    fn invalid_thresholds_are_rejected() {
+        let image = GrayImage::new(4, 4);
+        assert!(canny_edges(&image, 30.0, 20.0, Kernel::Sobel).is_err());
+        assert!(canny_edges(&image, -1.0, 20.0, Kernel::Sobel).is_err());
+        assert!(canny_edges(&image, 10.0, f32::NAN, Kernel::Sobel).is_err());
+        assert!(canny_edges(&image, 20.0, 20.0, Kernel::Sobel).is_ok());
+    }
+}
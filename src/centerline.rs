@@ -0,0 +1,148 @@
+use image::{GrayImage, Luma};
+use imageproc::{geometry::approximate_polygon_dp, point::Point};
+
+/// The offsets of the 8 neighbours, clockwise from the top one (P2 to P9 in the Zhang-Suen paper)
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// How many of the last points of a polyline are ignored when looking for a visited pixel to join at its end
+/// This avoids small hooks on the staircases of the skeleton
+const JOIN_IGNORED_POINTS: usize = 3;
+
+/// An open polyline in pixel coordinates, a closed loop has the same first and last point
+pub(crate) type Polyline = Vec<(f64, f64)>;
+
+/// Thins the ink (non-zero pixels) of a binary image to one pixel wide lines with the Zhang-Suen algorithm
+pub(crate) fn thin(binary: &GrayImage) -> GrayImage {
+    let mut skeleton = binary.clone();
+    loop {
+        let first_pass = thinning_pass(&mut skeleton, true);
+        let second_pass = thinning_pass(&mut skeleton, false);
+        if !first_pass && !second_pass {
+            break;
+        }
+    }
+    skeleton
+}
+
+/// One of the two sub-iterations of Zhang-Suen, returns whether a pixel has been removed
+fn thinning_pass(skeleton: &mut GrayImage, first_pass: bool) -> bool {
+    let mut to_remove = vec![];
+    for (x, y, pixel) in skeleton.enumerate_pixels() {
+        if pixel.0[0] == 0 {
+            continue;
+        }
+        let neighbours = NEIGHBOURS.map(|(dx, dy)| is_ink(skeleton, x as i32 + dx, y as i32 + dy));
+        let ink_neighbours = neighbours.iter().filter(|&&ink| ink).count();
+        let transitions = (0..8)
+            .filter(|&i| !neighbours[i] && neighbours[(i + 1) % 8])
+            .count();
+        let [top, _, right, _, bottom, _, left, _] = neighbours;
+        // P2 * P4 * P6 = 0 and P4 * P6 * P8 = 0 for the first pass, P2 * P4 * P8 = 0 and P2 * P6 * P8 = 0 for the second
+        let removable = if first_pass {
+            !(right && bottom && (top || left))
+        } else {
+            !(top && left && (right || bottom))
+        };
+        if (2..=6).contains(&ink_neighbours) && transitions == 1 && removable {
+            to_remove.push((x, y));
+        }
+    }
+    for &(x, y) in &to_remove {
+        skeleton.put_pixel(x, y, Luma([0]));
+    }
+    !to_remove.is_empty()
+}
+
+fn is_ink(image: &GrayImage, x: i32, y: i32) -> bool {
+    x >= 0
+        && y >= 0
+        && (x as u32) < image.width()
+        && (y as u32) < image.height()
+        && image.get_pixel(x as u32, y as u32).0[0] > 0
+}
+
+/// Follows the one pixel wide lines of a skeleton to build the polylines going through the center of the pixels
+/// The lines start from their free ends when they have one, so that each stroke is drawn in one go
+/// A line that reaches an already drawn one is joined to it, so the strokes stay connected
+/// The polylines are simplified so that they don't move away from the pixels by more than `tolerance`
+pub(crate) fn extract_polylines(skeleton: &GrayImage, tolerance: f64) -> Vec<Polyline> {
+    let width = skeleton.width();
+    let mut visited = vec![false; (width * skeleton.height()) as usize];
+    let index = |x: i32, y: i32| (y as u32 * width + x as u32) as usize;
+    let ink_neighbours = |x: i32, y: i32| {
+        NEIGHBOURS
+            .iter()
+            .filter(|(dx, dy)| is_ink(skeleton, x + dx, y + dy))
+            .count()
+    };
+
+    // free ends first, then everything left which is only made of loops
+    let ink_pixels: Vec<(i32, i32)> = skeleton
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0[0] > 0)
+        .map(|(x, y, _)| (x as i32, y as i32))
+        .collect();
+    let ends = ink_pixels
+        .iter()
+        .filter(|&&(x, y)| ink_neighbours(x, y) <= 1);
+    let seeds: Vec<(i32, i32)> = ends.chain(ink_pixels.iter()).copied().collect();
+
+    let mut polylines = vec![];
+    for (seed_x, seed_y) in seeds {
+        if visited[index(seed_x, seed_y)] {
+            continue;
+        }
+        visited[index(seed_x, seed_y)] = true;
+        let mut pixels = vec![Point::new(seed_x, seed_y)];
+        let (mut x, mut y) = (seed_x, seed_y);
+        loop {
+            // the orthogonal neighbours come first, so that the staircases are not skipped
+            let next = [0, 2, 4, 6, 1, 3, 5, 7]
+                .iter()
+                .map(|&i| (x + NEIGHBOURS[i].0, y + NEIGHBOURS[i].1))
+                .find(|&(nx, ny)| is_ink(skeleton, nx, ny) && !visited[index(nx, ny)]);
+            let Some((next_x, next_y)) = next else {
+                break;
+            };
+            visited[index(next_x, next_y)] = true;
+            pixels.push(Point::new(next_x, next_y));
+            (x, y) = (next_x, next_y);
+        }
+
+        // join the end of the line to an already drawn pixel, which also closes the loops
+        let recent = &pixels[pixels.len().saturating_sub(JOIN_IGNORED_POINTS)..];
+        let join = NEIGHBOURS
+            .iter()
+            .map(|(dx, dy)| Point::new(x + dx, y + dy))
+            .find(|point| {
+                is_ink(skeleton, point.x, point.y)
+                    && visited[index(point.x, point.y)]
+                    && !recent.contains(point)
+            });
+        if let Some(join) = join {
+            pixels.push(join);
+        }
+
+        let pixels = if pixels.len() > 2 {
+            approximate_polygon_dp(&pixels, tolerance.max(f64::EPSILON), false)
+        } else {
+            pixels
+        };
+        polylines.push(
+            pixels
+                .iter()
+                .map(|point| (point.x as f64 + 0.5, point.y as f64 + 0.5))
+                .collect(),
+        );
+    }
+    polylines
+}
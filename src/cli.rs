@@ -0,0 +1,743 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use crate::{
+    alpha::{AlphaParameters, Color},
+    config::{self, Preset},
+    despeckle::DespeckleParameters,
+    export::{Export, ExportParameters},
+    fdog::FdogParameters,
+    gradient::Kernel,
+    image_generation,
+    ink::Ink,
+    inputs::{self, InputFilter},
+    lineart::MethodParameters,
+    manifest::DirectoryManifest,
+    metadata,
+    method::MethodRegistry,
+    params::LineartParams,
+    prefilter::{Prefilter, PrefilterParameters},
+    summary::{GridBackground, SummaryParameters},
+    sweep::SweepAxis,
+    watch,
+    xdog::XdogParameters,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{
+    error::ErrorKind, parser::ValueSource, Arg, ArgMatches, Command, CommandFactory,
+    FromArgMatches, Parser,
+};
+use clap_verbosity_flag::{InfoLevel, Verbosity};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Debug, clap::Args, Serialize, Deserialize)]
+// not required so that `--list-methods` can be used alone and that the input can come from the configuration,
+// `run` checks that an input is given
+#[group(multiple = false)]
+struct Input {
+    /// The path to the input image. Mutually exclusive with `input_directory`
+    #[arg(long, short = 'i')]
+    input_image: Option<PathBuf>,
+    /// The path to the input directory where the PNG and JPEG images are, see `--recursive` to also use its subdirectories. Mutually exclusive with `input_image`
+    #[arg(long, short = 'd')]
+    input_directory: Option<PathBuf>,
+}
+
+/// The fields are also the keys of the configuration files, see `--config`
+#[derive(Parser, Serialize, Deserialize)]
+#[command(version, about)]
+struct Cli {
+    #[clap(flatten)]
+    #[serde(flatten)]
+    input: Input,
+    /// Also use the images of the subdirectories of `input_directory`, their linearts are written in the same subdirectories of `output_dir`
+    #[arg(long, short = 'r')]
+    recursive: bool,
+    /// Only use the images of `input_directory` whose path matches one of these glob patterns, like `chapter_*/*.png`
+    /// The paths are relative to `input_directory` and `*` also matches the `/` of the subdirectories
+    /// Can be given several times
+    #[arg(long, verbatim_doc_comment)]
+    include: Vec<String>,
+    /// Do not use the images of `input_directory` whose path matches one of these glob patterns, like `*_draft.*`
+    /// Can be given several times
+    #[arg(long, verbatim_doc_comment)]
+    exclude: Vec<String>,
+    /// A TOML or JSON configuration file (JSON if the extension is `.json`), the keys are the names of the options with underscores
+    /// For example `min_blur_radius = 4` or `export = ["svg", "gcode"]`
+    /// The options given on the command line take precedence over the file, which takes precedence over the preset
+    #[arg(long, verbatim_doc_comment)]
+    #[serde(skip)]
+    config: Option<PathBuf>,
+    /// A built-in set of options for a common style, the configuration file and the command line can change any of its options
+    #[arg(value_enum, long)]
+    #[serde(skip)]
+    preset: Option<Preset>,
+    /// Print the effective configuration as TOML, after applying the preset, the configuration file and the command line, then exit
+    /// The output can be used as a configuration file
+    #[arg(long, verbatim_doc_comment)]
+    #[serde(skip)]
+    print_config: bool,
+    /// The directory to output the images (if it doesn't exist, it will be created, recursively)
+    /// The actual path where the image will be is `output_dir`/image_name/
+    /// With the name of the image being extracted from the `input_image` path
+    #[arg(long, short, default_value_t = String::from("./multiple_images"), verbatim_doc_comment)]
+    output_dir: String,
+    /// The x size of the output image, this is used together with the `target_size_y`
+    /// We resize the image to keep the same image ratio and to get an area equals to target_size_x * target_size_y
+    /// It means that the actual output image might not have the exact target_size_x if the image ratio of the input is not the same as the target_size ratio
+    #[arg(long, short = 'x', default_value_t = 500, verbatim_doc_comment)]
+    target_size_x: u32,
+    /// The y size of the output image, this is used together with the `target_size_x`
+    /// We resize the image to keep the same image ratio and to get an area equals to target_size_x * target_size_y
+    /// It means that the actual output image might not have the exact target_size_y if the image ratio of the input is not the same as the target_size ratio
+    #[arg(long, short = 'y', default_value_t = 600, verbatim_doc_comment)]
+    target_size_y: u32,
+    /// An edge-preserving filter applied to the image before the method, to remove the noise and the textures that become speckles
+    /// Its strength is swept like the blur radius, each strength gets its own summary image
+    #[arg(value_enum, long, verbatim_doc_comment)]
+    prefilter: Option<Prefilter>,
+    /// The smallest strength of the pre-filter, only used with `--prefilter`
+    /// It is the radius of the window for the bilateral, median and Kuwahara filters and 5 iterations per level for the anisotropic diffusion
+    #[arg(long, default_value_t = 2, verbatim_doc_comment)]
+    min_prefilter_strength: u32,
+    /// How much to change the strength of the pre-filter between each image, only used with `--prefilter`
+    #[arg(long, default_value_t = 1)]
+    prefilter_step: u32,
+    /// How many different strengths of the pre-filter should be used, only used with `--prefilter`
+    /// For the strength i (between 0 and `prefilter_number`-1), the strength will be `min_prefilter_strength` + i * `prefilter_step`
+    #[arg(long, default_value_t = 1, verbatim_doc_comment)]
+    prefilter_number: u8,
+    /// The standard deviation (between 0 and 255) of the colour difference for the bilateral filter, only used with `--prefilter bilateral`
+    /// The higher it is, the more the edges are smoothed
+    #[arg(long, default_value_t = 30.0, verbatim_doc_comment)]
+    bilateral_sigma_color: f32,
+    /// The gradient (between 0 and 255) under which the anisotropic diffusion smooths the image, only used with `--prefilter anisotropic`
+    /// The higher it is, the more the edges are smoothed
+    #[arg(long, default_value_t = 20.0, verbatim_doc_comment)]
+    diffusion_kappa: f32,
+    /// The smallest blur radius that will be used by either the Gaussian blur. Note that both the Gaussian and the Sobel methods use a Gaussian blur
+    /// This can be used for both methods
+    #[arg(long, default_value_t = 3)]
+    min_blur_radius: i32,
+    /// How much to change the blur radius between each image
+    #[arg(long, default_value_t = 1)]
+    blur_step: i32,
+    /// How many different images should be made by varying the blur radius
+    /// For the image i (between 0 and `blur_number`-1), the blur radius will be `min_blur_radius` + i * `blur_step`
+    #[arg(long, default_value_t = 5, verbatim_doc_comment)]
+    blur_number: u8,
+    /// The lowest darken level that must be used. A darken level of n gives the same lines as blending the image n times with itself, which darkens the lines
+    /// The levels don't have to be whole numbers, 0 keeps the lines as they are
+    #[arg(long, default_value_t = 2.0, verbatim_doc_comment)]
+    min_darken_number: f32,
+    /// How much to increase the darken level for each new image when changing the darken, for example 0.5
+    #[arg(long, default_value_t = 1.0)]
+    darken_step: f32,
+    /// How many different images should be made by varying the darken
+    /// For the image i (between 0 and `darken_number`-1), the darken level will be `min_darken_number` + i * `darken_step`
+    #[arg(long, default_value_t = 4)]
+    darken_number: u8,
+    /// Sweep a parameter, as `name=start..end:step` (the end is included, `:step` defaults to 1) or `name=value,value,...`
+    /// The name is the one of an option with underscores, like `xdog_phi` or `transparency_threshold`,
+    /// `blur`, `darken` and `prefilter` for the strength of the pre-filter, `method`, or a parameter of the method
+    /// Can be given several times, every combination of the values gives an image, named after all the swept values
+    /// `blur`, `darken` and `prefilter` replace the ranges given by their min, step and number options
+    #[arg(long, verbatim_doc_comment)]
+    sweep: Vec<String>,
+    /// The swept parameter drawn as the rows of the summary images
+    /// There is one summary image for each combination of the parameters that are not the rows or the columns
+    #[arg(long, default_value_t = String::from("blur"), verbatim_doc_comment)]
+    grid_rows: String,
+    /// The swept parameter drawn as the columns of the summary images
+    #[arg(long, default_value_t = String::from("darken"))]
+    grid_columns: String,
+    /// What is drawn behind the images of the summary images, so that their transparent parts can be seen:
+    /// - checkerboard for grey and white squares
+    /// - #rrggbb for a solid colour
+    #[arg(long, default_value_t = GridBackground::default(), verbatim_doc_comment)]
+    grid_background: GridBackground,
+    /// The maximum width and height in pixels of the summary images, the images are downscaled to fit
+    /// The summary images have the full size of the images when it is not given
+    #[arg(long, value_parser = clap::value_parser!(u32).range(64..), verbatim_doc_comment)]
+    grid_max_size: Option<u32>,
+    /// A TrueType or OpenType font file for the labels of the summary images, instead of the embedded Exo 2
+    #[arg(long)]
+    grid_font: Option<PathBuf>,
+    /// Draw the source image, resized like the linearts, above the grid of the summary images and the HTML summary
+    #[arg(long)]
+    grid_source: bool,
+    /// The method to use when generating the lineart. Depending on your image, one method can work better than the other.
+    /// Use `--list-methods` to see what each method does
+    #[arg(long, short = 'm', default_value_t = String::from("gaussian"), verbatim_doc_comment)]
+    method: String,
+    /// Print the available methods with their description and parameters, then exit
+    #[arg(long, exclusive = true)]
+    #[serde(skip)]
+    list_methods: bool,
+    /// A parameter of the method, as `name=value`, for the methods that are not built in this crate
+    /// Can be given several times, `--list-methods` shows the parameters of each method
+    #[arg(long, value_parser = parse_method_parameter, verbatim_doc_comment)]
+    method_parameter: Vec<(String, f64)>,
+    /// The kernel used to compute the gradient of the image, used with `--method sobel`, `canny` and `fdog`
+    #[arg(value_enum, long, default_value_t = Kernel::Sobel)]
+    gradient_kernel: Kernel,
+    /// The low threshold of the hysteresis for the Canny method, only used with `--method canny`
+    /// Weak edges with a gradient magnitude above this threshold are kept if they are connected to a strong edge
+    /// The magnitude is in grey levels per pixel, a sharp edge from black to white has a magnitude of 255
+    #[arg(long, default_value_t = 12.5, verbatim_doc_comment)]
+    canny_low_threshold: f32,
+    /// The high threshold of the hysteresis for the Canny method, only used with `--method canny`
+    /// Edges with a gradient magnitude above this threshold are always kept
+    #[arg(long, default_value_t = 25.0, verbatim_doc_comment)]
+    canny_high_threshold: f32,
+    /// The ratio between the standard deviations of the two Gaussians for the XDoG method, only used with `--method xdog`
+    /// The standard deviation of the smallest Gaussian is half of the blur radius
+    #[arg(long, default_value_t = 1.6, verbatim_doc_comment)]
+    xdog_k: f32,
+    /// The sharpening factor p of the XDoG method, only used with `--method xdog`
+    /// The higher it is, the more the edges are emphasized
+    #[arg(long, default_value_t = 20.0, verbatim_doc_comment)]
+    xdog_sharpening: f32,
+    /// The soft threshold epsilon of the XDoG method (between 0 and 1), only used with `--method xdog`
+    /// The sharpened values above it become white, lower it to get fewer lines
+    #[arg(long, default_value_t = 0.1, verbatim_doc_comment)]
+    xdog_epsilon: f32,
+    /// The steepness phi of the soft threshold of the XDoG method, only used with `--method xdog`
+    /// The higher it is, the closer the lines are to pure black
+    #[arg(long, default_value_t = 10.0, verbatim_doc_comment)]
+    xdog_phi: f32,
+    /// The weight of the surrounding Gaussian in the DoG of the flow-based method, only used with `--method fdog`
+    /// The standard deviation of the DoG across the edges is half of the blur radius
+    #[arg(long, default_value_t = 0.99, verbatim_doc_comment)]
+    fdog_rho: f32,
+    /// The standard deviation of the Gaussian along the edge flow, only used with `--method fdog`
+    /// The higher it is, the longer and more continuous the lines are
+    #[arg(long, default_value_t = 3.0, verbatim_doc_comment)]
+    fdog_sigma_m: f32,
+    /// The threshold (between 0 and 1) used to binarize the flow-based DoG, only used with `--method fdog`
+    /// The higher it is, the more and the thicker the lines are, the lines mostly disappear below 0.95
+    #[arg(long, default_value_t = 0.99, verbatim_doc_comment)]
+    fdog_tau: f32,
+    /// The radius of the kernel used to smooth the edge tangent flow, only used with `--method fdog`
+    #[arg(long, default_value_t = 5)]
+    etf_radius: u32,
+    /// How many times the edge tangent flow is smoothed, only used with `--method fdog`
+    #[arg(long, default_value_t = 3)]
+    etf_iterations: u32,
+    /// The colour of the background in the linearts made by the methods, as #rrggbb, it is made transparent
+    /// The remaining colour of the lines is corrected so that they look the same when drawn over this colour
+    #[arg(long, default_value_t = Color::WHITE, verbatim_doc_comment)]
+    background_color: Color,
+    /// The pixels whose channels are all closer than this (between 0 and 255) to the background colour become fully transparent
+    /// Raise it to remove the light noise around the lines
+    #[arg(long, default_value_t = 0, verbatim_doc_comment)]
+    transparency_threshold: u8,
+    /// The pixels with a channel further than this (between 0 and 255) from the background colour stay fully opaque
+    /// Lower it to get darker and more solid lines
+    #[arg(long, default_value_t = 255, verbatim_doc_comment)]
+    opacity_threshold: u8,
+    /// Draw the linearts on this colour, as #rrggbb, instead of leaving a transparent background
+    #[arg(long)]
+    paper_color: Option<Color>,
+    /// Remove the pieces of ink with fewer pixels than this, like the dots left by the noise of the photos
+    /// The pixels are counted after the darken, 0 keeps every piece
+    #[arg(long, default_value_t = 0, verbatim_doc_comment)]
+    despeckle_area: u32,
+    /// Remove the strokes shorter than this, in pixels (measured along their centerline), 0 keeps every stroke
+    #[arg(long, default_value_t = 0.0)]
+    despeckle_length: f32,
+    /// Fill the holes inside the ink with at most this many pixels, 0 fills no hole
+    #[arg(long, default_value_t = 0)]
+    fill_holes_area: u32,
+    /// The colour of the lines, they stay black when it is not given:
+    /// - #rrggbb for a solid colour
+    /// - #rrggbb:#rrggbb for a gradient from the top to the bottom of the image
+    /// - source to tint each line with the hue of the image under it, like coloured pencils
+    #[arg(long, verbatim_doc_comment)]
+    ink: Option<Ink>,
+    /// The vector formats to export the linearts to, each file is written next to its PNG with the same name
+    /// Can be given several times or as a comma separated list
+    #[arg(
+        value_enum,
+        long,
+        short = 'e',
+        value_delimiter = ',',
+        verbatim_doc_comment
+    )]
+    export: Vec<Export>,
+    /// The maximum distance in pixels between the traced curves or strokes and the lineart when exporting to vector formats
+    /// A higher tolerance gives fewer and smoother curves, but loses details
+    #[arg(long, default_value_t = 0.5, verbatim_doc_comment)]
+    trace_tolerance: f64,
+    /// The size in millimeters of one pixel of the lineart on the paper, for the plotter formats (HPGL and G-code)
+    #[arg(long, default_value_t = 0.25)]
+    plotter_mm_per_pixel: f64,
+    /// How many images are generated at the same time, defaults to the number of cores
+    /// The blur radii of an image and the images of `input_directory` are generated in parallel
+    #[arg(long, short = 'j', value_parser = clap::value_parser!(u16).range(1..), verbatim_doc_comment)]
+    jobs: Option<u16>,
+    /// Generate every image and summary again, by default the outputs that are up to date with their input and the options are kept
+    /// and only the missing ones are generated, like the new images of an extended sweep
+    #[arg(long, verbatim_doc_comment)]
+    force: bool,
+    #[command(flatten)]
+    #[serde(skip)]
+    verbose: Verbosity<InfoLevel>,
+    #[command(subcommand)]
+    #[serde(skip)]
+    subcommand: Option<Subcommand>,
+}
+
+/// The options of the linearts are given before the subcommand, like `lineart_ify -d scans -o linearts watch`
+#[derive(Debug, clap::Subcommand)]
+enum Subcommand {
+    /// Generate the linearts of `input_directory`, then generate them again each time one of its images is added or changed, until stopped
+    /// The images whose outputs are up to date are skipped, see `--force`
+    #[command(verbatim_doc_comment)]
+    Watch {
+        /// How long in milliseconds an image must stay unchanged before its linearts are generated,
+        /// so that the images that are still being written or copied are not read
+        #[arg(long, default_value_t = 1000, verbatim_doc_comment)]
+        debounce: u64,
+    },
+    /// Print the parameters written in a lineart PNG and the command that generates it again
+    /// The options of the linearts are not used
+    #[command(verbatim_doc_comment)]
+    Inspect {
+        /// A lineart generated by lineart_ify
+        file: PathBuf,
+    },
+}
+
+/// Runs the command line with the methods of the registry, which can contain methods defined outside of this crate
+/// The exit code is a failure when the parameters are invalid or when the image cannot be generated
+pub fn run(registry: MethodRegistry) -> ExitCode {
+    let names = registry.names();
+    let mut command = Cli::command().mut_arg("method", |arg| {
+        let help = format!(
+            "{}\n[possible values: {}]",
+            arg.get_help().map(ToString::to_string).unwrap_or_default(),
+            names.join(", ")
+        );
+        arg.help(help)
+            .value_parser(move |name: &str| -> Result<String, String> {
+                if names.iter().any(|method| method == name) {
+                    Ok(name.to_owned())
+                } else {
+                    Err(format!("possible values: {}", names.join(", ")))
+                }
+            })
+    });
+    let matches = command.clone().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if cli.list_methods {
+        print_methods(&registry);
+        return ExitCode::SUCCESS;
+    }
+
+    env_logger::Builder::new()
+        .filter_level(cli.verbose.log_level_filter())
+        .init();
+
+    if let Some(Subcommand::Inspect { file }) = &cli.subcommand {
+        if let Err(e) = print_metadata(file) {
+            error!("{:?}: {:?}", file, e);
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let cli = match apply_config(cli, &matches) {
+        Ok(cli) => cli,
+        Err(e) => {
+            error!("Invalid configuration: {:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if cli.print_config {
+        return match config_to_toml(&cli) {
+            Ok(config) => {
+                print!("{}", config);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                error!("{:?}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if cli.input.input_image.is_none() && cli.input.input_directory.is_none() {
+        command
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "an --input-image or an --input-directory is required",
+            )
+            .exit();
+    }
+    // the value parser only checks the names given on the command line, not in the configuration
+    let Some(method) = registry.get(&cli.method) else {
+        error!(
+            "Unknown method {}, possible values: {}",
+            cli.method,
+            registry.names().join(", ")
+        );
+        return ExitCode::FAILURE;
+    };
+    let reproduction = match reproduction_command(&cli, &command) {
+        Ok(reproduction) => reproduction,
+        Err(e) => {
+            error!(
+                "Cannot write the command that reproduces the images: {:?}",
+                e
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+    let method_parameters = MethodParameters {
+        gradient_kernel: cli.gradient_kernel,
+        canny_thresholds: (cli.canny_low_threshold, cli.canny_high_threshold),
+        xdog: XdogParameters {
+            k: cli.xdog_k,
+            sharpening: cli.xdog_sharpening,
+            epsilon: cli.xdog_epsilon,
+            phi: cli.xdog_phi,
+        },
+        fdog: FdogParameters {
+            rho: cli.fdog_rho,
+            sigma_m: cli.fdog_sigma_m,
+            tau: cli.fdog_tau,
+            etf_radius: cli.etf_radius,
+            etf_iterations: cli.etf_iterations,
+        },
+        custom: cli.method_parameter.into_iter().collect(),
+    };
+    let prefilter = cli.prefilter.map(|filter| PrefilterParameters {
+        filter,
+        bilateral_sigma_color: cli.bilateral_sigma_color,
+        diffusion_kappa: cli.diffusion_kappa,
+    });
+    let alpha = AlphaParameters {
+        background: cli.background_color,
+        transparency_threshold: cli.transparency_threshold,
+        opacity_threshold: cli.opacity_threshold,
+        paper: cli.paper_color,
+    };
+    let export_parameters = ExportParameters {
+        formats: cli.export,
+        tolerance: cli.trace_tolerance,
+        millimeters_per_pixel: cli.plotter_mm_per_pixel,
+    };
+    let axes: Result<Vec<SweepAxis>> = cli
+        .sweep
+        .iter()
+        .map(|axis| SweepAxis::parse(axis, &registry))
+        .collect();
+    let axes = match axes {
+        Ok(axes) => axes,
+        Err(e) => {
+            error!("Invalid sweep: {:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let params = LineartParams::builder()
+        .target_size(cli.target_size_x, cli.target_size_y)
+        .min_prefilter_strength(cli.min_prefilter_strength)
+        .prefilter_step(cli.prefilter_step)
+        .prefilter_number(cli.prefilter_number)
+        .min_blur_radius(cli.min_blur_radius)
+        .blur_step(cli.blur_step)
+        .blur_number(cli.blur_number)
+        .min_darken_number(cli.min_darken_number)
+        .darken_step(cli.darken_step)
+        .darken_number(cli.darken_number)
+        .grid_axes(&cli.grid_rows, &cli.grid_columns)
+        .method(method)
+        .method_parameters(method_parameters)
+        .prefilter(prefilter)
+        .alpha(alpha)
+        .despeckle(DespeckleParameters {
+            min_area: cli.despeckle_area,
+            min_length: cli.despeckle_length,
+            max_hole_area: cli.fill_holes_area,
+        })
+        .ink(cli.ink)
+        .export_parameters(export_parameters)
+        .force(cli.force)
+        .summary(SummaryParameters {
+            command: reproduction,
+            background: cli.grid_background,
+            max_size: cli.grid_max_size,
+            font: cli.grid_font,
+            include_source: cli.grid_source,
+        });
+    let params = axes
+        .into_iter()
+        .fold(params, |params, axis| params.sweep_axis(axis))
+        .build();
+    let output_dir = PathBuf::from(cli.output_dir);
+
+    let params = match params {
+        Ok(params) => params,
+        Err(e) => {
+            error!("Invalid parameters: {:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    debug!("params: {:?}", params);
+    debug!("output_dir: {:?}", output_dir);
+    debug!("jobs: {:?}", cli.jobs);
+
+    if let Some(jobs) = cli.jobs {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs as usize)
+            .build_global()
+        {
+            error!("Cannot limit the number of jobs: {:?}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let filter = match InputFilter::new(cli.recursive, &cli.include, &cli.exclude) {
+        Ok(filter) => filter,
+        Err(e) => {
+            error!("{:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(Subcommand::Watch { debounce }) = cli.subcommand {
+        let Some(input_directory) = cli.input.input_directory else {
+            error!("watch needs an --input-directory to watch");
+            return ExitCode::FAILURE;
+        };
+        if let Err(e) = watch::watch(
+            &input_directory,
+            &filter,
+            &params,
+            &output_dir,
+            Duration::from_millis(debounce),
+        ) {
+            error!("{:?}: {:?}", input_directory, e);
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(input_image) = cli.input.input_image {
+        // the manifest is written even when some images failed, the error lists them
+        if let Err(e) =
+            image_generation::generate_images_and_grid(&input_image, &params, output_dir)
+        {
+            error!("{:?}: {:?}", input_image, e);
+            return ExitCode::FAILURE;
+        }
+    } else if let Some(input_directory) = cli.input.input_directory {
+        let input_images = match inputs::find_images(&input_directory, &filter, &output_dir) {
+            Ok(input_images) => input_images,
+            Err(e) => {
+                error!("{:?}: {:?}", input_directory, e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let manifest = DirectoryManifest {
+            input_directory,
+            inputs: image_generation::generate_inputs(&input_images, &params),
+        };
+        if let Err(e) = manifest.write(&output_dir) {
+            error!("Cannot write the manifest of {:?}: {:?}", output_dir, e);
+            return ExitCode::FAILURE;
+        }
+        // the errors of the images have already been logged
+        if manifest.inputs.iter().any(|input| input.error.is_some()) {
+            return ExitCode::FAILURE;
+        }
+    } else {
+        error!("No input image or input directory has been supplied, please use --help to see options, exiting.");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Fills the options that were not given on the command line with the preset, then with the configuration file
+fn apply_config(cli: Cli, matches: &ArgMatches) -> Result<Cli> {
+    let mut layers = vec![];
+    if let Some(preset) = cli.preset {
+        layers.push(preset.values()?);
+    }
+    if let Some(config) = &cli.config {
+        layers.push(config::read_config_file(config)?);
+    }
+    if layers.is_empty() {
+        return Ok(cli);
+    }
+
+    let from_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    // the input image and the input directory are mutually exclusive, so they are overridden together
+    let input_from_command_line =
+        from_command_line("input_image") || from_command_line("input_directory");
+    let Value::Object(mut values) = serde_json::to_value(&cli)? else {
+        bail!("The options cannot be converted to a configuration");
+    };
+    for layer in layers {
+        for (key, value) in layer {
+            if !values.contains_key(&key) {
+                bail!("Unknown option {} in the configuration", key);
+            }
+            let overridden = match key.as_str() {
+                "input_image" | "input_directory" => input_from_command_line,
+                _ => from_command_line(&key),
+            };
+            if !overridden {
+                values.insert(key, value);
+            }
+        }
+    }
+    let mut config: Cli = serde_json::from_value(Value::Object(values))
+        .context("Invalid value in the configuration")?;
+    // these options are not part of the configuration
+    config.print_config = cli.print_config;
+    config.verbose = cli.verbose;
+    config.subcommand = cli.subcommand;
+    Ok(config)
+}
+
+/// The options that are part of the configuration, by their name with underscores
+fn options_to_json(cli: &Cli) -> Result<Map<String, Value>> {
+    // going through the JSON text keeps the shortest representation of the f32 values, 0.1 instead of 0.10000000149011612
+    let Value::Object(values) = serde_json::from_str(&serde_json::to_string(cli)?)? else {
+        bail!("The options cannot be converted to a configuration");
+    };
+    Ok(values)
+}
+
+fn config_to_toml(cli: &Cli) -> Result<String> {
+    let mut values = options_to_json(cli)?;
+    // TOML has no null, the options without a value are left out
+    values.retain(|_, value| !value.is_null());
+    Ok(toml::to_string(&values)?)
+}
+
+/// The options of this run that differ from their defaults, once the preset and the configuration file are applied,
+/// without the input and the sweep, the HTML summary adds them back for each image
+/// The images are written to the `reproduce` directory of the output directory, so that they don't replace the images of this run
+fn reproduction_command(cli: &Cli, command: &Command) -> Result<Vec<String>> {
+    // they only change which images are generated, where and with how many threads, not how the images look
+    const SKIPPED: [&str; 9] = [
+        "input_image",
+        "input_directory",
+        "recursive",
+        "include",
+        "exclude",
+        "sweep",
+        "output_dir",
+        "force",
+        "jobs",
+    ];
+    let defaults = options_to_json(&Cli::try_parse_from([command.get_name()])?)?;
+    let mut arguments = vec![std::env::args()
+        .next()
+        .unwrap_or_else(|| command.get_name().to_owned())];
+    for (key, value) in options_to_json(cli)? {
+        if SKIPPED.contains(&key.as_str()) || defaults.get(&key) == Some(&value) {
+            continue;
+        }
+        let Some(long) = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == key.as_str())
+            .and_then(Arg::get_long)
+        else {
+            continue;
+        };
+        let option = format!("--{}", long);
+        match value {
+            Value::Null | Value::Bool(false) => {}
+            Value::Bool(true) => arguments.push(option),
+            Value::Array(values) => {
+                for value in values {
+                    arguments.push(option.clone());
+                    arguments.push(argument_value(value));
+                }
+            }
+            value => {
+                arguments.push(option);
+                arguments.push(argument_value(value));
+            }
+        }
+    }
+    arguments.push(String::from("--output-dir"));
+    let output_dir = Path::new(&cli.output_dir).join("reproduce");
+    arguments.push(output_dir.to_string_lossy().into_owned());
+    Ok(arguments)
+}
+
+/// A value of an option as written on the command line, `name=value` for the parameters of the methods
+fn argument_value(value: Value) -> String {
+    match value {
+        Value::String(value) => value,
+        Value::Array(values) => values
+            .into_iter()
+            .map(argument_value)
+            .collect::<Vec<_>>()
+            .join("="),
+        value => value.to_string(),
+    }
+}
+
+fn parse_method_parameter(parameter: &str) -> Result<(String, f64), String> {
+    let (name, value) = parameter
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=value, got {}", parameter))?;
+    let value = value
+        .parse()
+        .map_err(|e| format!("invalid value for {}: {}", name, e))?;
+    Ok((name.to_owned(), value))
+}
+
+/// Prints the text of the lineart by keyword, the parameters as indented JSON, and last the command that generates it again
+fn print_metadata(file: &Path) -> Result<()> {
+    let text = metadata::read_png_text(file)?;
+    let value = |keyword: &str| {
+        text.iter()
+            .find(|(key, _)| key == keyword)
+            .map(|(_, value)| value.as_str())
+    };
+    let Some(parameters) = value(metadata::PARAMETERS) else {
+        bail!("Not generated by lineart_ify, or by a version that did not write its parameters");
+    };
+    for keyword in [metadata::SOFTWARE, metadata::SOURCE, metadata::SOURCE_HASH] {
+        if let Some(value) = value(keyword) {
+            println!("{}: {}", keyword, value);
+        }
+    }
+    let parameters: Value = serde_json::from_str(parameters)?;
+    println!(
+        "{}: {}",
+        metadata::PARAMETERS,
+        serde_json::to_string_pretty(&parameters)?
+    );
+    match value(metadata::COMMAND) {
+        Some(command) => println!("{}: {}", metadata::COMMAND, command),
+        None => println!(
+            "{}: none, the lineart was not generated from the command line",
+            metadata::COMMAND
+        ),
+    }
+    Ok(())
+}
+
+fn print_methods(registry: &MethodRegistry) {
+    for method in registry.methods() {
+        println!("{}: {}", method.name(), method.description());
+        for parameter in method.parameters() {
+            println!(
+                "    --method-parameter {}={}: {}",
+                parameter.name, parameter.default, parameter.description
+            );
+        }
+    }
+}
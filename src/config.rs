@@ -0,0 +1,87 @@
+use std::{ffi::OsStr, fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+/// Sets of options for common styles, selected with `--preset`
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Preset {
+    /// Black ink lines with the XDoG method
+    Manga,
+    /// Soft grey strokes with the Gaussian method
+    Pencil,
+    /// Long and clean lines with the flow-based method, also exported as SVG
+    ColoringBook,
+}
+
+impl Preset {
+    /// The options of the preset, written like a configuration file
+    fn options(&self) -> &'static str {
+        match self {
+            Preset::Manga => {
+                r#"
+                method = "xdog"
+                min_blur_radius = 2
+                blur_step = 1
+                blur_number = 4
+                min_darken_number = 0
+                darken_step = 1
+                darken_number = 2
+                xdog_sharpening = 25.0
+                xdog_phi = 15.0
+                "#
+            }
+            Preset::Pencil => {
+                r#"
+                method = "gaussian"
+                min_blur_radius = 4
+                blur_step = 2
+                blur_number = 4
+                min_darken_number = 1
+                darken_step = 2
+                darken_number = 3
+                "#
+            }
+            Preset::ColoringBook => {
+                r#"
+                method = "fdog"
+                min_blur_radius = 3
+                blur_step = 2
+                blur_number = 3
+                min_darken_number = 0
+                darken_step = 1
+                darken_number = 2
+                fdog_sigma_m = 4.0
+                fdog_tau = 0.98
+                export = ["svg"]
+                "#
+            }
+        }
+    }
+
+    pub(crate) fn values(&self) -> Result<Map<String, Value>> {
+        parse_toml(self.options())
+    }
+}
+
+/// Reads the options of a configuration file, as JSON if its extension is `.json` and as TOML otherwise
+/// The keys are the names of the options with underscores, like `min_blur_radius`
+pub(crate) fn read_config_file(path: impl AsRef<Path>) -> Result<Map<String, Value>> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Cannot read the configuration file {:?}", path))?;
+    let values = if path.extension().and_then(OsStr::to_str) == Some("json") {
+        serde_json::from_str(&content).map_err(anyhow::Error::from)
+    } else {
+        parse_toml(&content)
+    };
+    values.with_context(|| format!("Cannot parse the configuration file {:?}", path))
+}
+
+fn parse_toml(content: &str) -> Result<Map<String, Value>> {
+    let table: toml::Table = toml::from_str(content)?;
+    match serde_json::to_value(table)? {
+        Value::Object(values) => Ok(values),
+        _ => bail!("A configuration must be a table of options"),
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+
+use image::{GrayImage, ImageBuffer, Luma};
+use imageproc::region_labelling::{connected_components, Connectivity};
+use photon_rs::PhotonImage;
+
+use crate::{centerline, trace};
+
+/// Which parts of the lineart are removed or filled after it is darkened, a value of 0 disables its step
+#[derive(Clone, Copy, Debug, Default, serde::Serialize)]
+pub struct DespeckleParameters {
+    /// The pieces of ink with fewer pixels than this are removed
+    pub min_area: u32,
+    /// The pieces of ink shorter than this, in pixels, are removed, their length is measured along their skeleton
+    /// It removes the short strokes that have too many pixels to be removed by `min_area`
+    pub min_length: f32,
+    /// The holes inside the ink with at most this many pixels are filled
+    pub max_hole_area: u32,
+}
+
+impl DespeckleParameters {
+    pub fn is_enabled(&self) -> bool {
+        self.min_area > 0 || self.min_length > 0_f32 || self.max_hole_area > 0
+    }
+}
+
+/// How many pixels were changed by [`despeckle`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DespeckleReport {
+    pub removed_pixels: u32,
+    pub filled_pixels: u32,
+}
+
+type Labels = ImageBuffer<Luma<u32>, Vec<u32>>;
+
+/// The size of a connected component of the binarized lineart
+#[derive(Clone, Copy, Default)]
+struct Component {
+    area: u32,
+    touches_border: bool,
+}
+
+impl Component {
+    fn add(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.area += 1;
+        self.touches_border |= x == 0 || y == 0 || x + 1 == width || y + 1 == height;
+    }
+}
+
+/// Labels the ink of the lineart (the same as the ink traced by the vector exports) into connected pieces,
+/// removes the pieces that are too small and fills the holes that are too small
+/// The faint pixels around a removed piece are removed with it, so that no halo is left,
+/// and a filled hole takes the average colour of the ink pixels on its border
+pub fn despeckle(
+    lineart: &PhotonImage,
+    parameters: &DespeckleParameters,
+) -> (PhotonImage, DespeckleReport) {
+    let mut report = DespeckleReport::default();
+    if !parameters.is_enabled() {
+        return (lineart.clone(), report);
+    }
+    let width = lineart.get_width();
+    let height = lineart.get_height();
+    let mut raw_pixels = lineart.get_raw_pixels();
+    let mut binary = trace::binarize(lineart);
+
+    if parameters.min_area > 0 || parameters.min_length > 0_f32 {
+        let labels = connected_components(&binary, Connectivity::Eight, Luma([0]));
+        let components = measure_components(&labels);
+        let lengths = if parameters.min_length > 0_f32 {
+            measure_lengths(&binary, &labels, components.len())
+        } else {
+            vec![]
+        };
+        let is_speckle = |label: u32| {
+            components[label as usize].area < parameters.min_area
+                || lengths
+                    .get(label as usize)
+                    .is_some_and(|&length| length < parameters.min_length)
+        };
+        let mut removed = GrayImage::new(width, height);
+        for (x, y, label) in labels.enumerate_pixels() {
+            if label.0[0] != 0 && is_speckle(label.0[0]) {
+                removed.put_pixel(x, y, Luma([255]));
+            }
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let is_removed = removed.get_pixel(x, y).0[0] != 0;
+                let is_halo = binary.get_pixel(x, y).0[0] == 0
+                    && neighbours(x, y, width, height)
+                        .any(|(nx, ny)| removed.get_pixel(nx, ny).0[0] != 0);
+                if is_removed || is_halo {
+                    let index = ((y * width + x) * 4) as usize;
+                    if raw_pixels[index + 3] != 0 {
+                        raw_pixels[index + 3] = 0;
+                        report.removed_pixels += 1;
+                    }
+                    binary.put_pixel(x, y, Luma([0]));
+                }
+            }
+        }
+    }
+
+    if parameters.max_hole_area > 0 {
+        let mut background = binary.clone();
+        imageproc::map::map_colors_mut(&mut background, |Luma([value])| Luma([255 - value]));
+        // the holes are only closed by a diagonal line of ink when the background is connected by its sides
+        let labels = connected_components(&background, Connectivity::Four, Luma([0]));
+        let components = measure_components(&labels);
+        let is_hole = |label: u32| {
+            let component = &components[label as usize];
+            !component.touches_border && component.area <= parameters.max_hole_area
+        };
+        // the sum of the ink pixels on the border of each hole, every ink pixel is counted once per hole it touches
+        let mut sums = vec![[0_u64; 4]; components.len()];
+        let mut counts = vec![0_u64; components.len()];
+        let mut counted = HashSet::new();
+        for (x, y, label) in labels.enumerate_pixels() {
+            let label = label.0[0];
+            if label == 0 || !is_hole(label) {
+                continue;
+            }
+            for (nx, ny) in neighbours(x, y, width, height) {
+                let index = ((ny * width + nx) * 4) as usize;
+                if binary.get_pixel(nx, ny).0[0] != 0 && counted.insert((label, index)) {
+                    for (sum, &value) in sums[label as usize]
+                        .iter_mut()
+                        .zip(&raw_pixels[index..index + 4])
+                    {
+                        *sum += value as u64;
+                    }
+                    counts[label as usize] += 1;
+                }
+            }
+        }
+        for (x, y, label) in labels.enumerate_pixels() {
+            let label = label.0[0] as usize;
+            if label == 0 || counts[label] == 0 {
+                continue;
+            }
+            let index = ((y * width + x) * 4) as usize;
+            for (channel, sum) in raw_pixels[index..index + 4].iter_mut().zip(sums[label]) {
+                *channel = (sum / counts[label]) as u8;
+            }
+            report.filled_pixels += 1;
+        }
+    }
+
+    (PhotonImage::new(raw_pixels, width, height), report)
+}
+
+/// The components indexed by their label, the label 0 is the background of the labelling and is left empty
+fn measure_components(labels: &Labels) -> Vec<Component> {
+    let (width, height) = labels.dimensions();
+    let max_label = labels.pixels().map(|label| label.0[0]).max().unwrap_or(0);
+    let mut components = vec![Component::default(); max_label as usize + 1];
+    for (x, y, label) in labels.enumerate_pixels() {
+        if label.0[0] != 0 {
+            components[label.0[0] as usize].add(x, y, width, height);
+        }
+    }
+    components
+}
+
+/// The length of each piece of ink, indexed by its label: the length of the lines of its skeleton plus one pixel,
+/// so that a dot has a length of 1 and a straight stroke of n pixels a length of n
+fn measure_lengths(binary: &GrayImage, labels: &Labels, component_number: usize) -> Vec<f32> {
+    let (width, height) = binary.dimensions();
+    let skeleton = centerline::thin(binary);
+    let mut lengths = vec![0_f32; component_number];
+    for (x, y, pixel) in skeleton.enumerate_pixels() {
+        if pixel.0[0] == 0 {
+            continue;
+        }
+        // every link between two pixels of the skeleton is seen from both of its ends, so each end adds half of it
+        let links: f32 = neighbours(x, y, width, height)
+            .filter(|&(nx, ny)| skeleton.get_pixel(nx, ny).0[0] != 0)
+            .map(|(nx, ny)| {
+                if nx == x || ny == y {
+                    0.5
+                } else {
+                    std::f32::consts::SQRT_2 / 2_f32
+                }
+            })
+            .sum();
+        lengths[labels.get_pixel(x, y).0[0] as usize] += links;
+    }
+    // the label 0 is not a piece of ink, a piece thinned away entirely still has the length of a dot
+    for length in lengths.iter_mut().skip(1) {
+        *length += 1_f32;
+    }
+    lengths
+}
+
+/// The 8 neighbours of a pixel that are inside the image
+fn neighbours(x: u32, y: u32, width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    (-1_i64..=1)
+        .flat_map(|dy| (-1_i64..=1).map(move |dx| (dx, dy)))
+        .filter(|&offset| offset != (0, 0))
+        .map(move |(dx, dy)| (x as i64 + dx, y as i64 + dy))
+        .filter(move |&(nx, ny)| nx >= 0 && ny >= 0 && nx < width as i64 && ny < height as i64)
+        .map(|(nx, ny)| (nx as u32, ny as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INK: [u8; 4] = [0, 0, 0, 255];
+    const PAPER: [u8; 4] = [255, 255, 255, 0];
+
+    /// A 20x20 transparent lineart, with the colours given by `ink` where it returns one
+    fn lineart(ink: impl Fn(u32, u32) -> Option<[u8; 4]>) -> PhotonImage {
+        let mut raw_pixels = vec![];
+        for y in 0..20 {
+            for x in 0..20 {
+                raw_pixels.extend(ink(x, y).unwrap_or(PAPER));
+            }
+        }
+        PhotonImage::new(raw_pixels, 20, 20)
+    }
+
+    fn pixel(image: &PhotonImage, x: u32, y: u32) -> [u8; 4] {
+        let index = ((y * 20 + x) * 4) as usize;
+        image.get_raw_pixels()[index..index + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn length_is_measured_along_the_skeleton() {
+        // a horizontal stroke of 12x2 pixels and a blob of 4x4 pixels, the diagonal of the blob (5.7) is longer than its skeleton
+        let image = lineart(|x, y| {
+            let stroke = (2..14).contains(&x) && (2..4).contains(&y);
+            let blob = (2..6).contains(&x) && (10..14).contains(&y);
+            (stroke || blob).then_some(INK)
+        });
+        let binary = trace::binarize(&image);
+        let labels = connected_components(&binary, Connectivity::Eight, Luma([0]));
+        let lengths = measure_lengths(&binary, &labels, 3);
+        let stroke = lengths[labels.get_pixel(2, 2).0[0] as usize];
+        let blob = lengths[labels.get_pixel(2, 10).0[0] as usize];
+        assert!((10_f32..=13_f32).contains(&stroke), "stroke: {}", stroke);
+        assert!(blob < 5_f32, "blob: {}", blob);
+
+        let parameters = DespeckleParameters {
+            min_length: 6_f32,
+            ..Default::default()
+        };
+        let (despeckled, report) = despeckle(&image, &parameters);
+        assert_eq!(pixel(&despeckled, 8, 2), INK);
+        assert_eq!(pixel(&despeckled, 3, 11)[3], 0);
+        assert_eq!(report.removed_pixels, 16);
+    }
+
+    #[test]
+    fn hole_takes_the_colour_of_its_border() {
+        // a red square ring with a 2x2 hole, and a blue line far from it that must not change the colour of the hole
+        let red = [200, 0, 0, 255];
+        let blue = [0, 0, 200, 255];
+        let image = lineart(|x, y| {
+            if (4..8).contains(&x) && (4..8).contains(&y) {
+                let is_hole = (5..7).contains(&x) && (5..7).contains(&y);
+                (!is_hole).then_some(red)
+            } else {
+                (x == 12).then_some(blue)
+            }
+        });
+        let parameters = DespeckleParameters {
+            max_hole_area: 4,
+            ..Default::default()
+        };
+        let (filled, report) = despeckle(&image, &parameters);
+        assert_eq!(report.filled_pixels, 4);
+        for (x, y) in [(5, 5), (6, 5), (5, 6), (6, 6)] {
+            assert_eq!(pixel(&filled, x, y), red);
+        }
+        // the background around the ink touches the border of the image, it is not a hole
+        assert_eq!(pixel(&filled, 0, 0), PAPER);
+    }
+}
@@ -0,0 +1,162 @@
+use image::{GrayImage, ImageBuffer, Luma};
+use imageproc::filter::gaussian_blur_f32;
+
+use crate::gradient::{self, Gradient, Kernel};
+
+/// Standard deviation of the Gaussian used to smooth the structure tensor before extracting the first tangents
+const STRUCTURE_TENSOR_SIGMA: f32 = 2.0;
+
+/// The edge tangent flow of an image: for each pixel, a unit vector following the edges (perpendicular to the gradient)
+/// and the normalized gradient magnitude (between 0 and 1) telling how strong the edge is at this pixel
+/// Pixels in flat areas have a null tangent
+pub(crate) struct EdgeTangentFlow {
+    width: u32,
+    height: u32,
+    tangents: Vec<(f32, f32)>,
+    magnitudes: Vec<f32>,
+}
+
+impl EdgeTangentFlow {
+    /// Computes the flow of a greyscale image, with its gradient computed with `kernel`
+    /// The first tangents come from the smoothed structure tensor, they are then smoothed `iterations` times
+    /// by averaging the tangents in a disk of radius `kernel_radius`, giving more weight to the neighbours with a stronger gradient
+    /// and with a similar direction, as described by Kang et al. in "Coherent Line Drawing"
+    pub(crate) fn new(
+        image: &GrayImage,
+        kernel_radius: u32,
+        iterations: u32,
+        kernel: Kernel,
+    ) -> EdgeTangentFlow {
+        let mut flow = EdgeTangentFlow::from_structure_tensor(image, kernel);
+        for _ in 0..iterations {
+            flow.smooth(kernel_radius);
+        }
+        flow
+    }
+
+    pub(crate) fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The unit tangent at the pixel, (0, 0) if there is no edge
+    pub(crate) fn tangent(&self, x: u32, y: u32) -> (f32, f32) {
+        self.tangents[self.index(x, y)]
+    }
+
+    /// The normalized gradient magnitude at the pixel, between 0 and 1
+    pub(crate) fn magnitude(&self, x: u32, y: u32) -> f32 {
+        self.magnitudes[self.index(x, y)]
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    fn from_structure_tensor(image: &GrayImage, kernel: Kernel) -> EdgeTangentFlow {
+        let width = image.width();
+        let height = image.height();
+        let gradient = Gradient::new(&gradient::gray_to_float(image), kernel);
+
+        // the three distinct components of the structure tensor [[E, F], [F, G]]
+        let component = |f: fn(f32, f32) -> f32| -> ImageBuffer<Luma<f32>, Vec<f32>> {
+            let tensor = ImageBuffer::from_fn(width, height, |x, y| {
+                let gx = gradient.x.get_pixel(x, y).0[0];
+                let gy = gradient.y.get_pixel(x, y).0[0];
+                Luma([f(gx, gy)])
+            });
+            gaussian_blur_f32(&tensor, STRUCTURE_TENSOR_SIGMA)
+        };
+        let tensor_e = component(|gx, _| gx * gx);
+        let tensor_f = component(|gx, gy| gx * gy);
+        let tensor_g = component(|_, gy| gy * gy);
+
+        let mut tangents = Vec::with_capacity((width * height) as usize);
+        let mut magnitudes = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let e = tensor_e.get_pixel(x, y).0[0];
+                let f = tensor_f.get_pixel(x, y).0[0];
+                let g = tensor_g.get_pixel(x, y).0[0];
+                // largest eigenvalue, its eigenvector is the gradient direction
+                let largest_eigenvalue =
+                    (e + g + ((e - g) * (e - g) + 4_f32 * f * f).sqrt()) / 2_f32;
+                if largest_eigenvalue <= f32::EPSILON {
+                    tangents.push((0_f32, 0_f32));
+                    magnitudes.push(0_f32);
+                    continue;
+                }
+                let gradient_angle = 0.5 * (2_f32 * f).atan2(e - g);
+                // the tangent is perpendicular to the gradient
+                tangents.push((-gradient_angle.sin(), gradient_angle.cos()));
+                magnitudes.push(largest_eigenvalue.sqrt());
+            }
+        }
+
+        let max_magnitude = magnitudes.iter().cloned().fold(0_f32, f32::max);
+        if max_magnitude > 0_f32 {
+            for magnitude in magnitudes.iter_mut() {
+                *magnitude /= max_magnitude;
+            }
+        }
+
+        EdgeTangentFlow {
+            width,
+            height,
+            tangents,
+            magnitudes,
+        }
+    }
+
+    /// One iteration of the edge tangent flow filter
+    fn smooth(&mut self, kernel_radius: u32) {
+        let radius = kernel_radius as i64;
+        let mut smoothed = Vec::with_capacity(self.tangents.len());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let tangent = self.tangent(x, y);
+                let magnitude = self.magnitude(x, y);
+                let mut sum = (0_f32, 0_f32);
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        if dx * dx + dy * dy > radius * radius {
+                            continue;
+                        }
+                        let neighbour_x = x as i64 + dx;
+                        let neighbour_y = y as i64 + dy;
+                        if neighbour_x < 0
+                            || neighbour_y < 0
+                            || neighbour_x >= self.width as i64
+                            || neighbour_y >= self.height as i64
+                        {
+                            continue;
+                        }
+                        let neighbour_tangent =
+                            self.tangent(neighbour_x as u32, neighbour_y as u32);
+                        let neighbour_magnitude =
+                            self.magnitude(neighbour_x as u32, neighbour_y as u32);
+                        // stronger edges pull the weaker ones
+                        let magnitude_weight =
+                            (1_f32 + (neighbour_magnitude - magnitude).tanh()) / 2_f32;
+                        // the sign flips the neighbour tangent when it points in the opposite direction
+                        let direction_weight =
+                            tangent.0 * neighbour_tangent.0 + tangent.1 * neighbour_tangent.1;
+                        let weight = magnitude_weight * direction_weight;
+                        sum.0 += neighbour_tangent.0 * weight;
+                        sum.1 += neighbour_tangent.1 * weight;
+                    }
+                }
+                let norm = (sum.0 * sum.0 + sum.1 * sum.1).sqrt();
+                if norm > f32::EPSILON {
+                    smoothed.push((sum.0 / norm, sum.1 / norm));
+                } else {
+                    smoothed.push(tangent);
+                }
+            }
+        }
+        self.tangents = smoothed;
+    }
+}
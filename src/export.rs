@@ -0,0 +1,222 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use image::GrayImage;
+use photon_rs::PhotonImage;
+
+use crate::{
+    centerline::{self, Polyline},
+    trace,
+};
+
+/// HPGL plotters move by steps of 0.025 mm
+const HPGL_UNITS_PER_MILLIMETER: f64 = 40.0;
+
+/// Height in millimeters of the pen above the paper when it moves without drawing in G-code
+const GCODE_PEN_UP_Z: f64 = 5.0;
+
+/// Speed in millimeters per minute of the pen when it draws in G-code
+const GCODE_FEED_RATE: u32 = 1000;
+
+/// The vector formats the linearts can be exported to, on top of the PNG
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Export {
+    /// Outlines of the lines, traced with Bezier curves, written to `.svg`
+    Svg,
+    /// Center of the lines as single strokes, written to `.centerline.svg`
+    CenterlineSvg,
+    /// Center of the lines as single strokes for pen plotters, written to `.hpgl`
+    Hpgl,
+    /// Center of the lines as single strokes for pen plotters and CNC machines, written to `.gcode`
+    Gcode,
+}
+
+impl Export {
+    /// The file the lineart is exported to, next to its PNG
+    pub(crate) fn path(&self, png_path: impl AsRef<Path>) -> PathBuf {
+        let mut path = png_path.as_ref().to_owned();
+        path.set_extension(self.extension());
+        path
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Export::Svg => "svg",
+            Export::CenterlineSvg => "centerline.svg",
+            Export::Hpgl => "hpgl",
+            Export::Gcode => "gcode",
+        }
+    }
+}
+
+/// How the linearts are exported, they don't depend on the blur radius or the darken
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ExportParameters {
+    pub formats: Vec<Export>,
+    /// Maximum distance in pixels between the traced curves and the pixels of the lineart
+    pub tolerance: f64,
+    /// Size of a pixel on the paper for the plotter formats
+    pub millimeters_per_pixel: f64,
+}
+
+impl Default for ExportParameters {
+    fn default() -> Self {
+        ExportParameters {
+            formats: vec![],
+            tolerance: 0.5,
+            millimeters_per_pixel: 0.25,
+        }
+    }
+}
+
+/// Writes the lineart in every requested format, next to its PNG: the file names are `png_path` with the extension of the format
+pub(crate) fn export_lineart(
+    image: &PhotonImage,
+    parameters: &ExportParameters,
+    png_path: impl AsRef<Path>,
+) -> Result<()> {
+    if parameters.formats.is_empty() {
+        return Ok(());
+    }
+    let binary = trace::binarize(image);
+    let width = image.get_width();
+    let height = image.get_height();
+    // the centerlines are shared by all the single stroke formats
+    let mut centerlines = None;
+    for format in &parameters.formats {
+        let content = match format {
+            Export::Svg => {
+                let paths = trace::trace_outlines(&binary, parameters.tolerance);
+                let path_data: Vec<String> =
+                    paths.iter().map(|path| path.to_svg_path_data()).collect();
+                format!(
+                    "{}<path fill=\"black\" fill-rule=\"evenodd\" d=\"{}\"/>\n</svg>\n",
+                    svg_header(width, height),
+                    path_data.join(" "),
+                )
+            }
+            Export::CenterlineSvg => polylines_to_svg(
+                centerlines_of(&mut centerlines, &binary, parameters.tolerance),
+                width,
+                height,
+            ),
+            Export::Hpgl => polylines_to_hpgl(
+                centerlines_of(&mut centerlines, &binary, parameters.tolerance),
+                height,
+                parameters.millimeters_per_pixel,
+            ),
+            Export::Gcode => polylines_to_gcode(
+                centerlines_of(&mut centerlines, &binary, parameters.tolerance),
+                height,
+                parameters.millimeters_per_pixel,
+            ),
+        };
+        fs::write(format.path(&png_path), content)?;
+    }
+    Ok(())
+}
+
+/// The centerlines are only computed the first time they are needed
+fn centerlines_of<'a>(
+    centerlines: &'a mut Option<Vec<Polyline>>,
+    binary: &GrayImage,
+    tolerance: f64,
+) -> &'a [Polyline] {
+    centerlines.get_or_insert_with(|| {
+        let skeleton = centerline::thin(binary);
+        centerline::extract_polylines(&skeleton, tolerance)
+    })
+}
+
+fn svg_header(width: u32, height: u32) -> String {
+    format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n")
+}
+
+fn polylines_to_svg(polylines: &[Polyline], width: u32, height: u32) -> String {
+    let mut svg = svg_header(width, height);
+    svg.push_str("<g fill=\"none\" stroke=\"black\" stroke-width=\"1\" stroke-linecap=\"round\" stroke-linejoin=\"round\">\n");
+    for polyline in polylines {
+        let points: Vec<String> = polyline
+            .iter()
+            .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+            .collect();
+        // a single point is drawn as a dot by the round line cap
+        let points = if points.len() == 1 {
+            vec![points[0].clone(), points[0].clone()]
+        } else {
+            points
+        };
+        let _ = writeln!(svg, "<polyline points=\"{}\"/>", points.join(" "));
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// The plotters have their origin at the bottom left of the paper, the images have it at the top left
+fn to_paper(point: (f64, f64), height: u32, millimeters_per_pixel: f64) -> (f64, f64) {
+    (
+        point.0 * millimeters_per_pixel,
+        (height as f64 - point.1) * millimeters_per_pixel,
+    )
+}
+
+fn polylines_to_hpgl(polylines: &[Polyline], height: u32, millimeters_per_pixel: f64) -> String {
+    let mut hpgl = String::from("IN;SP1;\n");
+    for polyline in polylines {
+        let units: Vec<(i64, i64)> = polyline
+            .iter()
+            .map(|&point| {
+                let (x, y) = to_paper(point, height, millimeters_per_pixel);
+                (
+                    (x * HPGL_UNITS_PER_MILLIMETER).round() as i64,
+                    (y * HPGL_UNITS_PER_MILLIMETER).round() as i64,
+                )
+            })
+            .collect();
+        let Some((first, rest)) = units.split_first() else {
+            continue;
+        };
+        let rest = if rest.is_empty() {
+            std::slice::from_ref(first)
+        } else {
+            rest
+        };
+        let coordinates: Vec<String> = rest.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+        let _ = writeln!(
+            hpgl,
+            "PU{},{};PD{};",
+            first.0,
+            first.1,
+            coordinates.join(",")
+        );
+    }
+    hpgl.push_str("PU;SP0;\n");
+    hpgl
+}
+
+fn polylines_to_gcode(polylines: &[Polyline], height: u32, millimeters_per_pixel: f64) -> String {
+    // millimeters, absolute positions, pen up
+    let mut gcode = format!("G21\nG90\nG0 Z{:.2}\n", GCODE_PEN_UP_Z);
+    for polyline in polylines {
+        let Some((&first, rest)) = polyline.split_first() else {
+            continue;
+        };
+        let (x, y) = to_paper(first, height, millimeters_per_pixel);
+        let _ = writeln!(gcode, "G0 X{:.3} Y{:.3}", x, y);
+        let _ = writeln!(gcode, "G1 Z0 F{}", GCODE_FEED_RATE);
+        for &point in rest {
+            let (x, y) = to_paper(point, height, millimeters_per_pixel);
+            let _ = writeln!(gcode, "G1 X{:.3} Y{:.3} F{}", x, y, GCODE_FEED_RATE);
+        }
+        let _ = writeln!(gcode, "G0 Z{:.2}", GCODE_PEN_UP_Z);
+    }
+    gcode.push_str("G0 X0 Y0\n");
+    gcode
+}
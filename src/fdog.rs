@@ -0,0 +1,186 @@
+use anyhow::{bail, Result};
+use image::{GrayImage, ImageBuffer, Luma};
+
+use crate::{edge_tangent_flow::EdgeTangentFlow, gradient::Kernel};
+
+/// Ratio between the standard deviations of the surrounding and the center Gaussians of the DoG
+const SURROUNDING_SIGMA_RATIO: f32 = 1.6;
+
+/// The parameters of the flow-based Difference of Gaussians, they don't depend on the blur radius
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct FdogParameters {
+    /// Weight of the surrounding Gaussian in the DoG, usually close to 1
+    pub rho: f32,
+    /// Standard deviation of the Gaussian along the flow, the higher it is the longer and more continuous the lines are
+    pub sigma_m: f32,
+    /// Threshold between 0 and 1 used to binarize the result, usually close to 1, the higher it is the thicker the lines are
+    pub tau: f32,
+    /// Radius of the kernel used to smooth the edge tangent flow
+    pub etf_radius: u32,
+    /// How many times the edge tangent flow is smoothed
+    pub etf_iterations: u32,
+}
+
+impl Default for FdogParameters {
+    fn default() -> Self {
+        FdogParameters {
+            rho: 0.99,
+            sigma_m: 3.0,
+            tau: 0.99,
+            etf_radius: 5,
+            etf_iterations: 3,
+        }
+    }
+}
+
+/// Runs the flow-based Difference of Gaussians (Kang et al., "Coherent Line Drawing") on a greyscale image
+/// A one dimensional DoG of standard deviation `sigma_c` is taken across the edges (along the gradient),
+/// then its results are accumulated with a Gaussian weight along the edge tangent flow, which keeps the lines continuous
+/// The edge tangent flow is computed from the gradient given by `kernel`
+/// The returned image has black lines on a white background
+pub(crate) fn fdog(
+    image: &GrayImage,
+    sigma_c: f32,
+    parameters: FdogParameters,
+    kernel: Kernel,
+) -> Result<GrayImage> {
+    check_parameters(&parameters)?;
+    let flow = EdgeTangentFlow::new(
+        image,
+        parameters.etf_radius,
+        parameters.etf_iterations,
+        kernel,
+    );
+    let gradient_dog = dog_along_gradient(image, &flow, sigma_c, parameters.rho);
+    let flow_dog = accumulate_along_flow(&gradient_dog, &flow, parameters.sigma_m);
+
+    let mut lines = GrayImage::new(image.width(), image.height());
+    for (x, y, pixel) in lines.enumerate_pixels_mut() {
+        let value = flow_dog.get_pixel(x, y).0[0];
+        *pixel = if value < 0_f32 && 1_f32 + value.tanh() < parameters.tau {
+            Luma([0])
+        } else {
+            Luma([255])
+        };
+    }
+    Ok(lines)
+}
+
+/// Checks that the parameters can be used by [`fdog`]
+pub(crate) fn check_parameters(parameters: &FdogParameters) -> Result<()> {
+    if !parameters.sigma_m.is_finite() || parameters.sigma_m <= 0_f32 {
+        bail!(
+            "The FDoG sigma_m must be a positive number, got {}",
+            parameters.sigma_m
+        );
+    }
+    for (name, value) in [("rho", parameters.rho), ("tau", parameters.tau)] {
+        if !(0_f32..=1_f32).contains(&value) {
+            bail!("The FDoG {} must be between 0 and 1, got {}", name, value);
+        }
+    }
+    Ok(())
+}
+
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    (-(x * x) / (2_f32 * sigma * sigma)).exp() / ((2_f32 * std::f32::consts::PI).sqrt() * sigma)
+}
+
+/// Bilinear interpolation of the intensity of the image between 0 and 1, the coordinates are clamped to the image
+fn sample(image: &GrayImage, x: f32, y: f32) -> f32 {
+    let max_x = (image.width() - 1) as f32;
+    let max_y = (image.height() - 1) as f32;
+    let x = x.clamp(0_f32, max_x);
+    let y = y.clamp(0_f32, max_y);
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let x1 = (x0 + 1_f32).min(max_x);
+    let y1 = (y0 + 1_f32).min(max_y);
+    let fx = x - x0;
+    let fy = y - y0;
+    let value = |x: f32, y: f32| image.get_pixel(x as u32, y as u32).0[0] as f32 / 255_f32;
+    let top = value(x0, y0) * (1_f32 - fx) + value(x1, y0) * fx;
+    let bottom = value(x0, y1) * (1_f32 - fx) + value(x1, y1) * fx;
+    top * (1_f32 - fy) + bottom * fy
+}
+
+/// One dimensional DoG across the edge at each pixel, on the intensities between 0 and 1 like Kang et al.
+/// so that the threshold `tau` applies to `1 + tanh` of values of the same scale for every image
+fn dog_along_gradient(
+    image: &GrayImage,
+    flow: &EdgeTangentFlow,
+    sigma_c: f32,
+    rho: f32,
+) -> ImageBuffer<Luma<f32>, Vec<f32>> {
+    let sigma_s = sigma_c * SURROUNDING_SIGMA_RATIO;
+    let half_length = (3_f32 * sigma_s).ceil() as i32;
+    let kernel: Vec<f32> = (-half_length..=half_length)
+        .map(|t| gaussian(t as f32, sigma_c) - rho * gaussian(t as f32, sigma_s))
+        .collect();
+
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let tangent = flow.tangent(x, y);
+        // the gradient is perpendicular to the tangent, any direction works in flat areas
+        let gradient = if tangent == (0_f32, 0_f32) {
+            (1_f32, 0_f32)
+        } else {
+            (tangent.1, -tangent.0)
+        };
+        let mut sum = 0_f32;
+        for (t, weight) in (-half_length..=half_length).zip(kernel.iter()) {
+            let sample_x = x as f32 + t as f32 * gradient.0;
+            let sample_y = y as f32 + t as f32 * gradient.1;
+            sum += sample(image, sample_x, sample_y) * weight;
+        }
+        Luma([sum])
+    })
+}
+
+/// Weighted average of the DoG along the flow curve going through each pixel, in both directions
+fn accumulate_along_flow(
+    gradient_dog: &ImageBuffer<Luma<f32>, Vec<f32>>,
+    flow: &EdgeTangentFlow,
+    sigma_m: f32,
+) -> ImageBuffer<Luma<f32>, Vec<f32>> {
+    let half_length = (3_f32 * sigma_m).ceil() as u32;
+    let max_x = (flow.width() - 1) as f32;
+    let max_y = (flow.height() - 1) as f32;
+
+    ImageBuffer::from_fn(flow.width(), flow.height(), |x, y| {
+        let center_weight = gaussian(0_f32, sigma_m);
+        let mut sum = gradient_dog.get_pixel(x, y).0[0] * center_weight;
+        let mut total_weight = center_weight;
+        for direction in [1_f32, -1_f32] {
+            let (mut position_x, mut position_y) = (x as f32, y as f32);
+            let start_tangent = flow.tangent(x, y);
+            let mut previous_tangent = (start_tangent.0 * direction, start_tangent.1 * direction);
+            for step in 1..=half_length {
+                let current_tangent =
+                    flow.tangent(position_x.round() as u32, position_y.round() as u32);
+                if current_tangent == (0_f32, 0_f32) {
+                    break;
+                }
+                // keep going in the same direction, the tangents are only defined up to their sign
+                let current_tangent = if current_tangent.0 * previous_tangent.0
+                    + current_tangent.1 * previous_tangent.1
+                    < 0_f32
+                {
+                    (-current_tangent.0, -current_tangent.1)
+                } else {
+                    current_tangent
+                };
+                position_x = (position_x + current_tangent.0).clamp(0_f32, max_x);
+                position_y = (position_y + current_tangent.1).clamp(0_f32, max_y);
+                previous_tangent = current_tangent;
+
+                let weight = gaussian(step as f32, sigma_m);
+                sum += gradient_dog
+                    .get_pixel(position_x.round() as u32, position_y.round() as u32)
+                    .0[0]
+                    * weight;
+                total_weight += weight;
+            }
+        }
+        Luma([sum / total_weight])
+    })
+}
@@ -0,0 +1,176 @@
+use image::{GrayImage, ImageBuffer, Luma};
+use photon_rs::PhotonImage;
+
+/// A single channel image with floating point values, usually between 0 and 1
+pub(crate) type FloatImage = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+/// The 3x3 kernel used to compute the gradient
+#[derive(Clone, Copy, Debug, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kernel {
+    Sobel,
+    Scharr,
+    Prewitt,
+}
+
+impl Kernel {
+    /// The kernel for the horizontal derivative, the vertical one is its transpose
+    /// The weights are divided by the sum of the positive weights so that a step of 1 in the image gives a derivative of 1
+    fn horizontal_weights(&self) -> [[f32; 3]; 3] {
+        let (side, center) = match self {
+            Kernel::Sobel => (1_f32, 2_f32),
+            Kernel::Scharr => (3_f32, 10_f32),
+            Kernel::Prewitt => (1_f32, 1_f32),
+        };
+        let norm = 2_f32 * side + center;
+        [
+            [-side / norm, 0_f32, side / norm],
+            [-center / norm, 0_f32, center / norm],
+            [-side / norm, 0_f32, side / norm],
+        ]
+    }
+}
+
+/// The horizontal and vertical derivatives of an image
+pub(crate) struct Gradient {
+    pub(crate) x: FloatImage,
+    pub(crate) y: FloatImage,
+}
+
+impl Gradient {
+    /// Computes the derivatives of the image with the given kernel, the pixels outside of the image are taken from the closest border
+    pub(crate) fn new(image: &FloatImage, kernel: Kernel) -> Gradient {
+        // an empty image has no border to take the pixels from
+        if image.width() == 0 || image.height() == 0 {
+            return Gradient {
+                x: FloatImage::new(image.width(), image.height()),
+                y: FloatImage::new(image.width(), image.height()),
+            };
+        }
+        let weights = kernel.horizontal_weights();
+        let width = image.width() as i64;
+        let height = image.height() as i64;
+        let value = |x: i64, y: i64| {
+            image
+                .get_pixel(x.clamp(0, width - 1) as u32, y.clamp(0, height - 1) as u32)
+                .0[0]
+        };
+        let convolve = |x: u32, y: u32, transpose: bool| {
+            let mut sum = 0_f32;
+            for (row, row_weights) in weights.iter().enumerate() {
+                for (column, weight) in row_weights.iter().enumerate() {
+                    let (dx, dy) = if transpose {
+                        (row as i64 - 1, column as i64 - 1)
+                    } else {
+                        (column as i64 - 1, row as i64 - 1)
+                    };
+                    sum += weight * value(x as i64 + dx, y as i64 + dy);
+                }
+            }
+            Luma([sum])
+        };
+        Gradient {
+            x: ImageBuffer::from_fn(image.width(), image.height(), |x, y| convolve(x, y, false)),
+            y: ImageBuffer::from_fn(image.width(), image.height(), |x, y| convolve(x, y, true)),
+        }
+    }
+
+    /// The magnitude of the gradient, scaled so that the strongest edge of the image has a magnitude of 1
+    /// A flat image gives a magnitude of 0 everywhere
+    pub(crate) fn normalized_magnitude(&self) -> FloatImage {
+        let mut magnitude = ImageBuffer::from_fn(self.x.width(), self.x.height(), |x, y| {
+            let gx = self.x.get_pixel(x, y).0[0];
+            let gy = self.y.get_pixel(x, y).0[0];
+            Luma([(gx * gx + gy * gy).sqrt()])
+        });
+        let max_magnitude = magnitude
+            .pixels()
+            .map(|pixel| pixel.0[0])
+            .fold(0_f32, f32::max);
+        if max_magnitude > 0_f32 {
+            for pixel in magnitude.pixels_mut() {
+                pixel.0[0] /= max_magnitude;
+            }
+        }
+        magnitude
+    }
+}
+
+/// The relative luminance (Rec. 709) of the image, between 0 and 1, the alpha channel is ignored
+pub(crate) fn luminance(image: &PhotonImage) -> FloatImage {
+    let raw_pixels = image.get_raw_pixels();
+    let mut luminance = FloatImage::new(image.get_width(), image.get_height());
+    for (pixel, rgba) in luminance.pixels_mut().zip(raw_pixels.chunks_exact(4)) {
+        let value = 0.2126 * rgba[0] as f32 + 0.7152 * rgba[1] as f32 + 0.0722 * rgba[2] as f32;
+        *pixel = Luma([value / 255_f32]);
+    }
+    luminance
+}
+
+/// The intensity of a greyscale image, between 0 and 1
+pub(crate) fn gray_to_float(image: &GrayImage) -> FloatImage {
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        Luma([image.get_pixel(x, y).0[0] as f32 / 255_f32])
+    })
+}
+
+/// Converts an image with values between 0 and 1 to an opaque greyscale image, the values outside are clamped
+pub(crate) fn float_to_photon(image: &FloatImage) -> PhotonImage {
+    let mut raw_pixels = Vec::with_capacity(image.len() * 4);
+    for pixel in image.pixels() {
+        let grey = (pixel.0[0].clamp(0_f32, 1_f32) * 255_f32).round() as u8;
+        raw_pixels.extend([grey, grey, grey, 255]);
+    }
+    PhotonImage::new(raw_pixels, image.width(), image.height())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image whose intensity is `left` before the column `step_x` and `right` from it
+    fn step(left: f32, right: f32, step_x: u32) -> FloatImage {
+        ImageBuffer::from_fn(10, 5, |x, _| Luma([if x < step_x { left } else { right }]))
+    }
+
+    #[test]
+    fn step_from_black_to_white_has_a_magnitude_of_1() {
+        for kernel in [Kernel::Sobel, Kernel::Scharr, Kernel::Prewitt] {
+            let gradient = Gradient::new(&step(0_f32, 1_f32, 5), kernel);
+            for (x, y, pixel) in gradient.x.enumerate_pixels() {
+                let expected = if (4..=5).contains(&x) { 1_f32 } else { 0_f32 };
+                assert!(
+                    (pixel.0[0] - expected).abs() < 1e-6,
+                    "{:?} at ({}, {})",
+                    kernel,
+                    x,
+                    y
+                );
+            }
+            assert!(gradient.y.pixels().all(|pixel| pixel.0[0].abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn normalized_magnitude_keeps_fractions() {
+        // a step of 0.25 then a step of 0.5, the first one is half as strong as the strongest edge
+        let image = ImageBuffer::from_fn(10, 5, |x, _| {
+            Luma([match x {
+                0..=2 => 0_f32,
+                3..=6 => 0.25,
+                _ => 0.75,
+            }])
+        });
+        let magnitude = Gradient::new(&image, Kernel::Sobel).normalized_magnitude();
+        assert!((magnitude.get_pixel(2, 2).0[0] - 0.5).abs() < 1e-6);
+        assert!((magnitude.get_pixel(6, 2).0[0] - 1_f32).abs() < 1e-6);
+        assert_eq!(magnitude.get_pixel(0, 2).0[0], 0_f32);
+    }
+
+    #[test]
+    fn empty_image_has_an_empty_gradient() {
+        let gradient = Gradient::new(&FloatImage::new(0, 3), Kernel::Sobel);
+        assert_eq!(gradient.x.dimensions(), (0, 3));
+        assert_eq!(gradient.normalized_magnitude().dimensions(), (0, 3));
+    }
+}
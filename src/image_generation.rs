@@ -1,48 +1,190 @@
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
+    time::Instant,
 };
 
-use crate::lineart::{self, Method};
-use ab_glyph::FontRef;
-use anyhow::{Context, Result};
-use image::{ExtendedColorType, ImageBuffer, ImageFormat, Rgba};
-use imageproc::drawing::draw_text_mut;
-use log::{debug, info};
-use photon_rs::{
-    multiple::blend,
-    native::{open_image, save_image},
-    transform,
+use crate::{
+    alpha,
+    cache::{self, Cache},
+    despeckle, export, ink,
+    inputs::InputImage,
+    lineart,
+    manifest::{self, ImageManifest, InputManifest, Manifest, Timings},
+    metadata,
+    params::LineartParams,
+    pipeline, prefilter,
+    summary::{self, SummaryParameters},
+    sweep::{self, SweepSpec, Variant},
 };
+use ab_glyph::FontArc;
+use anyhow::{anyhow, Context, Result};
+use image::{
+    imageops::{self, FilterType},
+    ImageFormat, Rgba, RgbaImage,
+};
+use imageproc::drawing::{draw_text_mut, text_size};
+use log::{debug, error, info, warn};
+use photon_rs::{transform, PhotonImage};
+use rayon::prelude::*;
+
+/// A lineart generated by [`generate_all_images`], with how long it took and why it failed
+pub(crate) struct GeneratedImage<'a> {
+    pub(crate) variant: &'a Variant,
+    pub(crate) timings: Timings,
+    pub(crate) error: Option<anyhow::Error>,
+}
 
-pub(crate) fn generate_all_images(
+/// Generates the images of the variants, which are all variants of `params`
+/// `base_image` is the image of `base_image_path` already resized to the target size
+/// An image that cannot be written doesn't stop the others, its error is returned with it
+/// Every PNG has its parameters in its metadata, with `source_hash` the hash of the file of the base image, see [`metadata`]
+pub(crate) fn generate_all_images<'a>(
     base_image_path: impl AsRef<Path>,
-    target_size: (u32, u32),
-    min_blur_radius: i32,
-    blur_step: i32,
-    blur_number: u8,
-    min_darken_number: u8,
-    darken_step: u8,
-    darken_number: u8,
-    method: Method,
-    output_dir: impl AsRef<Path>,
-) -> Result<PathBuf> {
+    base_image: &PhotonImage,
+    source_hash: &str,
+    params: &LineartParams,
+    variants: &[&'a Variant],
+    output_dir_for_images: &Path,
+) -> Result<Vec<GeneratedImage<'a>>> {
     let base_image_path_ref = base_image_path.as_ref();
     info!("Generating all images for {:?}", base_image_path_ref);
-    let output_dir_for_images = build_image_directory_path(base_image_path_ref, output_dir)?;
 
     // create directory if it doesn't exist
     let directory_exists = output_dir_for_images.try_exists()?;
     if !directory_exists {
-        fs::create_dir_all(&output_dir_for_images)?;
+        fs::create_dir_all(output_dir_for_images)?;
     }
-    let base_image = open_image(base_image_path_ref)?;
-    //make the image smaller if it's necessary
+    let sweep = &params.sweep;
+    let blur_axis = sweep.axis_index(sweep::BLUR);
+    let darken_axis = sweep.axis_index(sweep::DARKEN);
+    // the variants that only differ by their blur radius and darken level share the same pre-filtered image,
+    // and the ones that only differ by their darken level share the same lineart
+    // every group is independent, so they all run in parallel
+    let generated = group_variants(variants.iter().copied(), &[blur_axis, darken_axis])
+        .par_iter()
+        .flat_map(|source_group| {
+            let start = Instant::now();
+            let first = source_group[0];
+            let source_image = match &first.params.prefilter {
+                Some(prefilter) => {
+                    prefilter::apply_prefilter(base_image, first.prefilter_strength, prefilter)
+                }
+                None => base_image.clone(),
+            };
+            let prefilter_time = milliseconds(start);
+            group_variants(source_group.iter().copied(), &[darken_axis])
+                .par_iter()
+                .flat_map(|lineart_group| {
+                    let start = Instant::now();
+                    let first = lineart_group[0];
+                    let original_image = first.params.method.apply(
+                        source_image.clone(),
+                        first.blur_radius,
+                        &first.params.method_parameters,
+                    );
+                    let method_time = milliseconds(start);
+                    let original_image = match original_image {
+                        Ok(image) => alpha::image_color_to_alpha(&image, &first.params.alpha),
+                        // every image of the group is made from this lineart, they all fail with its error
+                        Err(e) => {
+                            return lineart_group
+                                .iter()
+                                .map(|&variant| GeneratedImage {
+                                    variant,
+                                    timings: Timings {
+                                        prefilter: prefilter_time,
+                                        method: method_time,
+                                        finish: 0,
+                                    },
+                                    error: Some(anyhow!("{:#}", e)),
+                                })
+                                .collect::<Vec<_>>();
+                        }
+                    };
+                    lineart_group
+                        .par_iter()
+                        .map(|&variant| {
+                            let start = Instant::now();
+                            let result = finish_image(
+                                variant,
+                                &original_image,
+                                &source_image,
+                                output_dir_for_images,
+                                base_image_path_ref,
+                                source_hash,
+                            );
+                            GeneratedImage {
+                                variant,
+                                timings: Timings {
+                                    prefilter: prefilter_time,
+                                    method: method_time,
+                                    finish: milliseconds(start),
+                                },
+                                error: result.err(),
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    info!(
+        "Finished generating all images for {:?}",
+        base_image_path_ref
+    );
+    Ok(generated)
+}
+
+/// Darkens the lineart made by the method for the variant, removes its speckles, exports it, adds the ink and the paper and saves it
+/// `source_image` is the pre-filtered image the lineart was made from
+fn finish_image(
+    variant: &Variant,
+    original_image: &PhotonImage,
+    source_image: &PhotonImage,
+    output_dir_for_images: &Path,
+    base_image_path: &Path,
+    source_hash: &str,
+) -> Result<()> {
+    let params = &variant.params;
+    let image = lineart::darken(original_image, variant.darken);
+    let save_path = build_image_output_path(output_dir_for_images, &variant.name)?;
+    debug!("{}", save_path);
+    // the speckles are removed after the darken, which can make faint speckles visible
+    let (image, report) = despeckle::despeckle(&image, &params.despeckle);
+    if params.despeckle.is_enabled() {
+        debug!(
+            "{}: removed {} pixels of speckles, filled {} pixels of holes",
+            save_path, report.removed_pixels, report.filled_pixels
+        );
+    }
+    // the vector formats are traced before the ink and the paper are added,
+    // a light ink would be taken for the background and a dark paper for ink
+    export::export_lineart(&image, &params.export_parameters, &save_path)?;
+    let image = match params.ink {
+        Some(line_ink) => ink::apply_ink(&image, line_ink, source_image),
+        None => image,
+    };
+    let image = match params.alpha.paper {
+        Some(paper) => alpha::image_on_paper(&image, paper),
+        None => image,
+    };
+    let text = metadata::lineart_metadata(variant, base_image_path, source_hash)?;
+    metadata::save_png(&image, &save_path, &text)?;
+    Ok(())
+}
+
+fn milliseconds(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+/// Makes the image smaller if its area is bigger than `target_size.0 * target_size.1`, keeping its ratio
+/// Images that are already small enough are returned as they are
+pub fn resize_to_target_area(image: PhotonImage, target_size: (u32, u32)) -> PhotonImage {
     let target_area = target_size.0.saturating_mul(target_size.1);
-    let current_area = base_image
-        .get_width()
-        .saturating_mul(base_image.get_height());
-    let base_image = if current_area > target_area {
+    let current_area = image.get_width().saturating_mul(image.get_height());
+    if current_area > target_area {
         // calculate the ratio needed to get to the target area
         // the correct length ratio is the square root of the area ratio because:
         // if we name t the target area, c the current area,
@@ -52,58 +194,39 @@ pub(crate) fn generate_all_images(
         // N.y = c.y * sqrt(t/c) = c.y * sqrt(t.x * t.y)/sqrt(c.x * c.y) = sqrt(c.y)/sqrt(c.x) * sqrt(t.x * t.y)
         // N = N.x * N.y = sqrt(c.x)/sqrt(c.y) * sqrt(c.y)/sqrt(c.x) * sqrt(t.x * t.y) * sqrt(t.x * t.y) = t.x * t.y = t
         let ratio = (target_area as f64).sqrt() / (current_area as f64).sqrt();
-        let new_width = base_image.get_width() as f64 * ratio;
-        let new_height = base_image.get_height() as f64 * ratio;
+        let new_width = image.get_width() as f64 * ratio;
+        let new_height = image.get_height() as f64 * ratio;
         transform::resize(
-            &base_image,
+            &image,
             new_width as u32,
             new_height as u32,
             transform::SamplingFilter::Lanczos3,
         )
     } else {
-        //not very good, we are re-assigning an existing image
-        // this is due to the fact that transform returns an image
-        base_image
-    };
-    for blur_index in 0..blur_number {
-        let blur_radius = min_blur_radius + (blur_index as i32 * blur_step);
-        let original_image = match method {
-            Method::Gaussian => lineart::gaussian_blend_dodge(base_image.clone(), blur_radius),
-            Method::Sobel => lineart::sobel_blend_dodge(base_image.clone(), blur_radius),
-        };
-        let mut image = original_image.clone();
-        //blend the image a first time
-        for _ in 0..min_darken_number {
-            blend(&mut image, &original_image, "multiply")
-        }
-        for darken_index in 0..(darken_number - 1) {
-            let darken = min_darken_number + darken_index * darken_step;
-            let save_path = build_image_output_path(&output_dir_for_images, blur_radius, darken)?;
-            debug!("{}", save_path);
-            save_image(image.clone(), save_path.as_str())?;
-            for _ in 0..darken_step {
-                blend(&mut image, &original_image, "multiply")
-            }
-        }
-        let save_path = build_image_output_path(
-            &output_dir_for_images,
-            blur_radius,
-            min_darken_number + (darken_number - 1) * darken_step,
-        )?;
+        image
+    }
+}
 
-        save_image(image, save_path.as_str())?; // save image for last iteration
+/// Groups the variants that have the same values on every axis but the ignored ones, in the order of the sweep
+fn group_variants<'a>(
+    variants: impl Iterator<Item = &'a Variant>,
+    ignored_axes: &[Option<usize>],
+) -> Vec<Vec<&'a Variant>> {
+    let mut groups: BTreeMap<Vec<usize>, Vec<&Variant>> = BTreeMap::new();
+    for variant in variants {
+        let mut key = variant.indices.clone();
+        for &axis in ignored_axes.iter().flatten() {
+            key[axis] = 0;
+        }
+        groups.entry(key).or_default().push(variant);
     }
-    info!(
-        "Finished generating all images for {:?}",
-        base_image_path_ref
-    );
-    Ok(output_dir_for_images)
+    groups.into_values().collect()
 }
 
-fn build_image_output_path(image_dir: impl AsRef<Path>, blur: i32, darken: u8) -> Result<String> {
+pub(crate) fn build_image_output_path(image_dir: impl AsRef<Path>, name: &str) -> Result<String> {
     let mut save_path = image_dir.as_ref().to_owned();
-    save_path.push(format!("blur_{}_darken_{}", blur, darken));
-    save_path.set_extension("png");
+    // the extension is part of the name, `set_extension` would replace the decimals of a fractional value
+    save_path.push(format!("{}.png", name));
     let save_path = save_path.to_str().with_context(|| {
         format!(
             "The path to save the image cannot be converted to a string: {:?}",
@@ -128,156 +251,506 @@ fn build_image_directory_path(
     Ok(output_dir_for_images)
 }
 
+/// Draws the images of one facet of the sweep in a grid, with the rows and the columns axes of the sweep
+/// `facet` gives the values of the other axes, its indices for the rows, the columns and the blocks are not used
+/// When several methods are swept, each method gets its own block of columns, side by side, see [`SweepSpec::blocks`]
+/// `source` is drawn above the grid as a reference when it is given, with the size of the linearts
+/// Every row is as high as its highest image and every column as wide as its widest image, the images are centered in their cell
 pub(crate) fn generate_image_grid(
-    min_blur_radius: i32,
-    blur_step: i32,
-    blur_number: u8,
-    min_darken_number: u8,
-    darken_step: u8,
-    darken_number: u8,
+    sweep: &SweepSpec,
+    facet: &[usize],
+    summary: &SummaryParameters,
+    source: Option<&RgbaImage>,
     input_dir: impl AsRef<Path>,
 ) -> Result<()> {
-    info!("Starting generation of summary image");
-    let right_padding_mult: f32 = 1.2;
-    let down_padding_mult: f32 = 1.1;
-    let top_padding_mult: f32 = 0.6;
-    let left_padding_mult: f32 = 1.3;
-
-    //load a first image to get the dimensions and extrapolate the size of the final image
-    let first_image_path = build_image_output_path(&input_dir, min_blur_radius, min_darken_number)?;
-    let first_image = open_image(first_image_path.as_str())?;
-    let first_width = first_image.get_width();
-    let first_height = first_image.get_height();
-    let left_padding = (first_width as f32) * left_padding_mult;
-    let top_padding = (first_height as f32) * top_padding_mult;
-    let total_width =
-        (first_width as f32 * right_padding_mult) * (darken_number as f32) + left_padding; // darken by rows
-    let total_height =
-        (first_height as f32 * down_padding_mult) * (blur_number as f32) + top_padding; // blur by columns
-    let total_width = total_width as u32;
-    let total_height = total_height as u32;
-
-    // data for the text
-    let pixel_to_repeat: Rgba<u8> = Rgba([255, 255, 255, 255]);
-    let mut canvas = ImageBuffer::from_pixel(total_width, total_height, pixel_to_repeat);
-    let font = FontRef::try_from_slice(include_bytes!("../fonts/Exo2-Light.otf"))?;
+    info!(
+        "Starting generation of summary image for {:?}",
+        input_dir.as_ref()
+    );
+    let rows = &sweep.axes()[sweep.rows()];
+    let columns = &sweep.axes()[sweep.columns()];
+    let blocks = sweep.blocks().map(|block_axis| &sweep.axes()[block_axis]);
+    let block_count = blocks.map_or(1, |axis| axis.values().len());
+    let mut labels = GridLabels {
+        row_title: axis_title(rows.name()),
+        column_title: axis_title(columns.name()),
+        block_titles: blocks
+            .map(|axis| {
+                axis.values()
+                    .iter()
+                    .map(|value| format!("{}: {}", axis_title(axis.name()), value))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        row_labels: vec![],
+        column_labels: columns.values().iter().map(ToString::to_string).collect(),
+    };
+    let mut images = vec![];
+    if let Some(source) = source {
+        labels.row_labels.push(String::from("Source"));
+        images.push(vec![source.clone()]);
+    }
+    for (row_index, row_value) in rows.values().iter().enumerate() {
+        labels.row_labels.push(row_value.to_string());
+        let mut row_images = vec![];
+        for block_index in 0..block_count {
+            for column_index in 0..columns.values().len() {
+                let mut indices = facet.to_vec();
+                indices[sweep.rows()] = row_index;
+                indices[sweep.columns()] = column_index;
+                if let Some(block_axis) = sweep.blocks() {
+                    indices[block_axis] = block_index;
+                }
+                let fetch_path =
+                    build_image_output_path(&input_dir, &sweep.variant_name(&indices))?;
+                row_images.push(image::ImageReader::open(fetch_path)?.decode()?.to_rgba8());
+            }
+        }
+        images.push(row_images);
+    }
+    let font = load_font(summary.font.as_deref());
     let text_color = Rgba([0_u8, 0_u8, 0_u8, 255_u8]);
-    let scale = (first_width as f32) / 3_f32;
-
-    // constant positions for the text
-    let blur_text_x = (left_padding / 2_f32) as i32;
-    let darken_text_position_y = top_padding as i32 - (first_height as f32 / 3_f32) as i32;
 
-    for blur_index in 0..blur_number {
-        let blur_radius = min_blur_radius + (blur_index as i32 * blur_step);
-        let image_y =
-            ((first_height as f32 * down_padding_mult) * (blur_index as f32) + top_padding) as i64;
-        if blur_index == 0 {
-            draw_text_mut(
-                &mut canvas,
-                text_color,
-                0,
-                image_y as i32,
-                scale,
-                &font,
-                "Blur",
-            );
-
-            let darken_text_position_x = (first_width as f32 / 3_f32) as i32;
-            draw_text_mut(
-                &mut canvas,
-                text_color,
-                darken_text_position_x,
-                darken_text_position_y,
-                scale,
-                &font,
-                "Darken",
-            );
+    // the thumbnails are made smaller until the grid fits in the maximum size, the labels depend on their size
+    let mut thumbnail_scale = 1_f32;
+    let layout = loop {
+        let layout = GridLayout::new(&images, thumbnail_scale, &font, &labels);
+        let Some(max_size) = summary.max_size else {
+            break layout;
+        };
+        let overflow =
+            (layout.width as f32 / max_size as f32).max(layout.height as f32 / max_size as f32);
+        if overflow <= 1_f32 || thumbnail_scale < 0.01 {
+            break layout;
         }
-        let blur_text_y = image_y as i32 + (first_height as f32 / 2_f32) as i32;
+        // a bit more than needed, the labels don't shrink as fast as the thumbnails
+        thumbnail_scale /= overflow * 1.05;
+    };
 
+    let mut canvas = RgbaImage::from_pixel(layout.width, layout.height, Rgba([255, 255, 255, 255]));
+    let mut draw_text = |text: &str, x: u32, y: u32| {
         draw_text_mut(
             &mut canvas,
             text_color,
-            blur_text_x,
-            blur_text_y,
-            scale,
+            x as i32,
+            y as i32,
+            layout.font_size,
             &font,
-            format!("{}", blur_radius).as_str(),
+            text,
+        );
+    };
+    let centered = |text: &str, x: u32, width: u32| {
+        x + width.saturating_sub(text_size(layout.font_size, &font, text).0) / 2
+    };
+    for block_index in 0..block_count {
+        let (block_x, block_width) = layout.block_span(block_index, labels.column_labels.len());
+        if let (Some(title), Some(title_y)) =
+            (labels.block_titles.get(block_index), layout.block_title_y)
+        {
+            draw_text(title, centered(title, block_x, block_width), title_y);
+        }
+        draw_text(
+            &labels.column_title,
+            centered(&labels.column_title, block_x, block_width),
+            layout.column_title_y,
         );
+        for (column_index, label) in labels.column_labels.iter().enumerate() {
+            let column = block_index * labels.column_labels.len() + column_index;
+            let x = centered(label, layout.column_x[column], layout.column_widths[column]);
+            draw_text(label, x, layout.labels_y);
+        }
+    }
+    draw_text(&labels.row_title, layout.margin, layout.labels_y);
+    for (row_index, label) in labels.row_labels.iter().enumerate() {
+        let y = layout.row_y[row_index]
+            + layout.row_heights[row_index].saturating_sub(layout.line_height) / 2;
+        draw_text(label, layout.margin, y);
+    }
+    for (row_index, row_images) in images.iter().enumerate() {
+        let row_y = layout.row_y[row_index];
+        let row_height = layout.row_heights[row_index];
+        for (column_index, image) in row_images.iter().enumerate() {
+            let width = scaled(image.width(), thumbnail_scale);
+            let height = scaled(image.height(), thumbnail_scale);
+            let x =
+                layout.column_x[column_index] + (layout.column_widths[column_index] - width) / 2;
+            let y = row_y + (row_height - height) / 2;
+            summary.background.fill(&mut canvas, x, y, width, height);
+            if thumbnail_scale < 1_f32 {
+                let thumbnail = imageops::resize(image, width, height, FilterType::Triangle);
+                imageops::overlay(&mut canvas, &thumbnail, x as i64, y as i64);
+            } else {
+                imageops::overlay(&mut canvas, image, x as i64, y as i64);
+            }
+        }
+    }
+
+    let mut canvas_dir_out = input_dir.as_ref().to_owned();
+    canvas_dir_out.push(format!("{}.png", sweep.summary_name(facet)));
+    canvas.save_with_format(canvas_dir_out, ImageFormat::Png)?;
+    info!(
+        "Finished generating summary image for {:?}",
+        input_dir.as_ref()
+    );
+    Ok(())
+}
 
-        for darken_index in 0..darken_number {
-            let darken = min_darken_number + darken_index * darken_step;
-            let fetch_path = build_image_output_path(&input_dir, blur_radius, darken)?;
-            let image = image::ImageReader::open(fetch_path)?.decode()?;
-            let image_x =
-                (first_width as f32 * right_padding_mult) * (darken_index as f32) + left_padding;
-            image::imageops::overlay(&mut canvas, &image, image_x as i64, image_y);
+/// The texts of a summary image
+struct GridLabels {
+    row_title: String,
+    column_title: String,
+    /// One title for each block of columns, empty when there is a single block
+    block_titles: Vec<String>,
+    row_labels: Vec<String>,
+    /// The labels of the columns of one block
+    column_labels: Vec<String>,
+}
 
-            if blur_index == 0 {
-                let darken_text_position_x = image_x as i32 + (first_width as f32 / 2_f32) as i32;
-                draw_text_mut(
-                    &mut canvas,
-                    text_color,
-                    darken_text_position_x,
-                    darken_text_position_y,
-                    scale,
-                    &font,
-                    format!("{}", darken).as_str(),
-                );
+/// The positions of the cells and the labels of a summary image, in pixels
+struct GridLayout {
+    width: u32,
+    height: u32,
+    font_size: f32,
+    line_height: u32,
+    margin: u32,
+    block_title_y: Option<u32>,
+    column_title_y: u32,
+    labels_y: u32,
+    /// The widths and positions of the columns of every block, one block after the other
+    column_widths: Vec<u32>,
+    column_x: Vec<u32>,
+    row_heights: Vec<u32>,
+    row_y: Vec<u32>,
+}
+
+impl GridLayout {
+    /// The rows of `images` can have fewer images than the columns, like the row of the source
+    fn new(
+        images: &[Vec<RgbaImage>],
+        thumbnail_scale: f32,
+        font: &FontArc,
+        labels: &GridLabels,
+    ) -> Self {
+        let column_count = labels.column_labels.len() * labels.block_titles.len().max(1);
+        let row_heights: Vec<u32> = images
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|image| scaled(image.height(), thumbnail_scale))
+                    .max()
+                    .unwrap_or(1)
+            })
+            .collect();
+        let column_widths: Vec<u32> = (0..column_count)
+            .map(|column| {
+                images
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|image| scaled(image.width(), thumbnail_scale))
+                    .max()
+                    .unwrap_or(1)
+            })
+            .collect();
+        // the text is sized after the thumbnails, but stays readable on small thumbnails and small on big ones
+        let average_height = row_heights.iter().sum::<u32>() as f32 / row_heights.len() as f32;
+        let font_size = (average_height / 8_f32).clamp(14_f32, 64_f32);
+        let line_height = text_size(font_size, font, "Xy").1.max(font_size as u32);
+        let margin = (font_size / 2_f32) as u32;
+        let gap = margin;
+
+        let widest_row_label = labels
+            .row_labels
+            .iter()
+            .chain([&labels.row_title])
+            .map(|label| text_size(font_size, font, label).0)
+            .max()
+            .unwrap_or(0);
+        let left = widest_row_label + margin * 2;
+        let mut top = margin;
+        let block_title_y = (!labels.block_titles.is_empty()).then(|| {
+            top += line_height + margin;
+            margin
+        });
+        let column_title_y = top;
+        let labels_y = top + line_height + margin;
+        top = labels_y + line_height + margin;
+
+        let mut column_x = vec![];
+        let mut x = left;
+        for (column, &column_width) in column_widths.iter().enumerate() {
+            // the blocks are further apart than the columns
+            if column > 0 && column % labels.column_labels.len().max(1) == 0 {
+                x += gap * 3;
             }
+            column_x.push(x);
+            x += column_width + gap;
         }
+        let mut row_y = vec![];
+        let mut y = top;
+        for &row_height in &row_heights {
+            row_y.push(y);
+            y += row_height + gap;
+        }
+        let mut layout = GridLayout {
+            width: x,
+            height: y,
+            font_size,
+            line_height,
+            margin,
+            block_title_y,
+            column_title_y,
+            labels_y,
+            column_widths,
+            column_x,
+            row_heights,
+            row_y,
+        };
+        // the titles can be wider than their block
+        let last_block = labels.block_titles.len().max(1) - 1;
+        let (last_block_x, _) = layout.block_span(last_block, labels.column_labels.len());
+        let widest_title = labels
+            .block_titles
+            .iter()
+            .chain([&labels.column_title])
+            .map(|title| text_size(font_size, font, title).0)
+            .max()
+            .unwrap_or(0);
+        layout.width = layout.width.max(last_block_x + widest_title + margin);
+        layout
     }
 
-    let mut canvas_dir_out = input_dir.as_ref().to_owned();
-    canvas_dir_out.push("summary");
-    canvas_dir_out.set_extension("png");
+    /// The left and the width of a block of columns
+    fn block_span(&self, block: usize, columns_per_block: usize) -> (u32, u32) {
+        let first = block * columns_per_block;
+        let last = first + columns_per_block.max(1) - 1;
+        let x = self.column_x[first];
+        (x, self.column_x[last] + self.column_widths[last] - x)
+    }
+}
 
-    image::save_buffer_with_format(
-        canvas_dir_out,
-        &canvas,
-        total_width,
-        total_height,
-        ExtendedColorType::Rgba8,
-        ImageFormat::Png,
-    )?;
-    info!("Finished generating summary image\n\n");
-    Ok(())
+fn scaled(length: u32, scale: f32) -> u32 {
+    ((length as f32 * scale).round() as u32).max(1)
+}
+
+/// The font of the summary images, the embedded Exo 2 when no font is given or when it cannot be read
+fn load_font(path: Option<&Path>) -> FontArc {
+    if let Some(path) = path {
+        match fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| FontArc::try_from_vec(bytes).map_err(anyhow::Error::from))
+        {
+            Ok(font) => return font,
+            Err(e) => warn!(
+                "Cannot use the font {:?}, using the default font instead: {:?}",
+                path, e
+            ),
+        }
+    }
+    FontArc::try_from_slice(DEFAULT_FONT).expect("the embedded font is valid")
 }
 
+const DEFAULT_FONT: &[u8] = include_bytes!("../fonts/Exo2-Light.otf");
+
+/// The title of an axis in the summary images, its name with a capital letter and spaces followed by its unit, like `Blur (px)`
+pub(crate) fn axis_title(name: &str) -> String {
+    let title = name.replace('_', " ");
+    let mut characters = title.chars();
+    let title: String = match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => title,
+    };
+    match sweep::unit(name) {
+        Some(unit) => format!("{} ({})", title, unit),
+        None => title,
+    }
+}
+
+/// Generates the linearts of the image and their summaries in a directory of `output_dir` named like the image, without its extension
 pub fn generate_images_and_grid(
     base_image_path: impl AsRef<Path>,
-    target_size: (u32, u32),
-    min_blur_radius: i32,
-    blur_step: i32,
-    blur_number: u8,
-    min_darken_number: u8,
-    darken_step: u8,
-    darken_number: u8,
-    method: Method,
+    params: &LineartParams,
     output_dir: impl AsRef<Path>,
 ) -> Result<()> {
-    let output_dir_for_images = generate_all_images(
-        base_image_path,
-        target_size,
-        min_blur_radius,
-        blur_step,
-        blur_number,
-        min_darken_number,
-        darken_step,
-        darken_number,
-        method,
-        &output_dir,
-    )?;
-    generate_image_grid(
-        min_blur_radius,
-        blur_step,
-        blur_number,
-        min_darken_number,
-        darken_step,
-        darken_number,
-        output_dir_for_images,
-    )?;
+    let output_dir_for_images = build_image_directory_path(&base_image_path, output_dir)?;
+    generate_images_and_grid_in(base_image_path, params, output_dir_for_images)
+}
 
-    Ok(())
+/// Generates the linearts of the image and their summaries directly in `output_dir_for_images`,
+/// like the images found by [`find_images`](crate::inputs::find_images) with their own output directory
+/// The outputs that are up to date with the image and `params` are kept, unless `params.force` is set,
+/// so extending a sweep only generates the new images (and the summaries again)
+pub fn generate_images_and_grid_in(
+    base_image_path: impl AsRef<Path>,
+    params: &LineartParams,
+    output_dir_for_images: impl AsRef<Path>,
+) -> Result<()> {
+    let start = Instant::now();
+    let base_image_path = base_image_path.as_ref();
+    let output_dir_for_images = output_dir_for_images.as_ref();
+    let input =
+        fs::read(base_image_path).with_context(|| format!("Cannot read {:?}", base_image_path))?;
+    let mut cache = if params.force {
+        Cache::new(&input)
+    } else {
+        Cache::read(output_dir_for_images, &input)
+    };
+    let variants = params.variants()?;
+    let outdated: Vec<&Variant> = variants
+        .iter()
+        .filter(|variant| !cache.is_current(variant, base_image_path, output_dir_for_images))
+        .collect();
+    if outdated.is_empty() && cache.is_summary_current(params, output_dir_for_images) {
+        info!(
+            "{:?} is up to date in {:?}, use --force to generate it again",
+            base_image_path, output_dir_for_images
+        );
+        return Ok(());
+    }
+    // the image is decoded and resized once, for the linearts and for the summaries
+    let source = image::load_from_memory(&input)
+        .with_context(|| format!("Cannot decode {:?}", base_image_path))?;
+    let source = resize_to_target_area(pipeline::to_photon(&source), params.target_size);
+    let previous_manifest = Manifest::read(output_dir_for_images);
+    let mut generated = vec![];
+    if !outdated.is_empty() {
+        debug!(
+            "{:?}: generating {} of the {} images",
+            base_image_path,
+            outdated.len(),
+            variants.len()
+        );
+        generated = generate_all_images(
+            base_image_path,
+            &source,
+            &cache::hash(&input),
+            params,
+            &outdated,
+            output_dir_for_images,
+        )?;
+        for image in &generated {
+            if image.error.is_none() {
+                cache.set_current(image.variant, base_image_path);
+            }
+        }
+        cache.write(output_dir_for_images)?;
+    }
+
+    let mut images = vec![];
+    for variant in &variants {
+        let (timings, error) = match generated
+            .iter()
+            .find(|image| image.variant.name == variant.name)
+        {
+            Some(image) => (
+                Some(image.timings),
+                image.error.as_ref().map(|e| format!("{:#}", e)),
+            ),
+            // kept from a previous run
+            None => (
+                previous_manifest
+                    .iter()
+                    .flat_map(|manifest| &manifest.images)
+                    .find(|image| image.name == variant.name)
+                    .and_then(|image| image.timings),
+                None,
+            ),
+        };
+        images.push(ImageManifest::new(
+            variant,
+            output_dir_for_images,
+            timings,
+            error,
+        )?);
+    }
+    let mut manifest = Manifest {
+        source: base_image_path.to_owned(),
+        output_dir: output_dir_for_images.to_owned(),
+        width: source.get_width(),
+        height: source.get_height(),
+        method: params.method.name().to_owned(),
+        sweep: manifest::sweep_values(params),
+        images,
+        summaries: vec![],
+        milliseconds: 0,
+    };
+    let failed = generated
+        .iter()
+        .filter(|image| image.error.is_some())
+        .count();
+    let summaries = if failed > 0 {
+        Err(anyhow!(
+            "{} of the {} images could not be generated, see {:?}",
+            failed,
+            generated.len(),
+            output_dir_for_images.join(manifest::MANIFEST_FILE_NAME)
+        ))
+    } else {
+        generate_summaries(base_image_path, &source, params, output_dir_for_images)
+    };
+    manifest.milliseconds = milliseconds(start);
+    match summaries {
+        Ok(summaries) => {
+            manifest.summaries = summaries;
+            manifest.write()?;
+            cache.set_summary_current(params);
+            cache.write(output_dir_for_images)?;
+            Ok(())
+        }
+        Err(e) => {
+            manifest.write()?;
+            Err(e)
+        }
+    }
+}
+
+/// Generates the summary images and the HTML summary, and returns their paths
+fn generate_summaries(
+    base_image_path: &Path,
+    source: &PhotonImage,
+    params: &LineartParams,
+    output_dir_for_images: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut summaries = vec![];
+    let source_tile = params
+        .summary
+        .include_source
+        .then(|| pipeline::from_photon(source.clone()));
+    for facet in params.sweep.facets() {
+        generate_image_grid(
+            &params.sweep,
+            &facet,
+            &params.summary,
+            source_tile.as_ref(),
+            output_dir_for_images,
+        )?;
+        summaries
+            .push(output_dir_for_images.join(format!("{}.png", params.sweep.summary_name(&facet))));
+    }
+    summary::generate_html_summary(base_image_path, source, params, output_dir_for_images)?;
+    summaries.push(output_dir_for_images.join("summary.html"));
+    Ok(summaries)
+}
+
+/// Generates the linearts of the images in parallel, the error of an image is logged and doesn't stop the others
+/// Returns the entries of the images for the manifest of their directory
+pub fn generate_inputs(input_images: &[InputImage], params: &LineartParams) -> Vec<InputManifest> {
+    input_images
+        .par_iter()
+        .map(|input_image| {
+            let start = Instant::now();
+            let result =
+                generate_images_and_grid_in(&input_image.path, params, &input_image.output_dir);
+            if let Err(e) = &result {
+                error!("{:?}: {:?}", input_image.path, e);
+            }
+            let manifest_path = input_image.output_dir.join(manifest::MANIFEST_FILE_NAME);
+            InputManifest {
+                source: input_image.path.clone(),
+                output_dir: input_image.output_dir.clone(),
+                manifest: manifest_path.is_file().then_some(manifest_path),
+                milliseconds: milliseconds(start),
+                error: result.err().map(|e| format!("{:#}", e)),
+            }
+        })
+        .collect()
 }
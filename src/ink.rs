@@ -0,0 +1,112 @@
+use std::{fmt, str::FromStr};
+
+use photon_rs::PhotonImage;
+
+use crate::alpha::Color;
+
+/// The lightest the lines can be with [`Ink::Source`], so that they stay visible on the light parts of the image
+const MAX_SOURCE_LIGHTNESS: f32 = 0.45;
+
+/// The colour of the lines, written on the command line and in the configuration files as:
+/// - `#rrggbb` for a solid colour
+/// - `#rrggbb:#rrggbb` for a gradient from the top to the bottom of the image
+/// - `source` to tint each line with the hue of the image under it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Ink {
+    Solid(Color),
+    Gradient { top: Color, bottom: Color },
+    Source,
+}
+
+impl FromStr for Ink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "source" {
+            return Ok(Ink::Source);
+        }
+        match s.split_once(':') {
+            Some((top, bottom)) => Ok(Ink::Gradient {
+                top: top.parse()?,
+                bottom: bottom.parse()?,
+            }),
+            None => Ok(Ink::Solid(s.parse()?)),
+        }
+    }
+}
+
+impl TryFrom<String> for Ink {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for Ink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ink::Solid(color) => write!(f, "{}", color),
+            Ink::Gradient { top, bottom } => write!(f, "{}:{}", top, bottom),
+            Ink::Source => write!(f, "source"),
+        }
+    }
+}
+
+impl From<Ink> for String {
+    fn from(ink: Ink) -> Self {
+        ink.to_string()
+    }
+}
+
+/// Replaces the colour of the lines by the ink, their alpha is kept
+/// `source` is the image the lineart was made from, with the same size, it is only read with [`Ink::Source`]
+pub fn apply_ink(lineart: &PhotonImage, ink: Ink, source: &PhotonImage) -> PhotonImage {
+    let width = lineart.get_width() as usize;
+    let height = lineart.get_height() as usize;
+    let source_pixels = source.get_raw_pixels();
+    let mut raw_pixels = lineart.get_raw_pixels();
+    for (index, pixel) in raw_pixels.chunks_exact_mut(4).enumerate() {
+        let color = match ink {
+            Ink::Solid(color) => color.0,
+            Ink::Gradient { top, bottom } => {
+                let position = (index / width) as f32 / (height.max(2) - 1) as f32;
+                std::array::from_fn(|channel| {
+                    let value = top.0[channel] as f32
+                        + (bottom.0[channel] as f32 - top.0[channel] as f32) * position;
+                    value.round() as u8
+                })
+            }
+            Ink::Source => match source_pixels.get(index * 4..index * 4 + 3) {
+                Some(&[red, green, blue]) => source_hue([red, green, blue]),
+                _ => [0, 0, 0],
+            },
+        };
+        pixel[0..3].copy_from_slice(&color);
+    }
+    PhotonImage::new(raw_pixels, lineart.get_width(), lineart.get_height())
+}
+
+/// The colour with the hue and the saturation of the source pixel, darkened to at most [`MAX_SOURCE_LIGHTNESS`]
+fn source_hue(pixel: [u8; 3]) -> [u8; 3] {
+    let [red, green, blue] = pixel.map(|channel| channel as f32 / 255_f32);
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let lightness = (max + min) / 2_f32;
+    if lightness <= MAX_SOURCE_LIGHTNESS {
+        return pixel;
+    }
+    // in HSL, moving every channel relative to the lightness keeps the hue, and the saturation is kept
+    // when the chroma is scaled by the same factor as min(lightness, 1 - lightness)
+    let range = lightness.min(1_f32 - lightness);
+    let chroma_scale = if range > 0_f32 {
+        MAX_SOURCE_LIGHTNESS.min(1_f32 - MAX_SOURCE_LIGHTNESS) / range
+    } else {
+        0_f32
+    };
+    [red, green, blue].map(|channel| {
+        let value = MAX_SOURCE_LIGHTNESS + (channel - lightness) * chroma_scale;
+        (value.clamp(0_f32, 1_f32) * 255_f32).round() as u8
+    })
+}
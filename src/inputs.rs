@@ -0,0 +1,206 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use log::warn;
+
+/// The extensions of the images that are read from an input directory, in any case
+const IMAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+/// Which files of an input directory are used
+#[derive(Clone, Debug, Default)]
+pub struct InputFilter {
+    /// Whether the images of the subdirectories are used too, their linearts are written in the same subdirectories of the output
+    pub recursive: bool,
+    /// The images must match one of these patterns when there are any
+    pub include: Vec<Pattern>,
+    /// The images that match one of these patterns are not used
+    pub exclude: Vec<Pattern>,
+}
+
+impl InputFilter {
+    /// The patterns are matched against the paths relative to the input directory, like `chapter_1/page_2.png`,
+    /// `*` also matches the `/` between the directories so `*.png` matches the PNG images of every subdirectory
+    pub fn new(recursive: bool, include: &[String], exclude: &[String]) -> Result<Self> {
+        let parse = |patterns: &[String]| -> Result<Vec<Pattern>> {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern)
+                        .with_context(|| format!("Invalid glob pattern {}", pattern))
+                })
+                .collect()
+        };
+        Ok(InputFilter {
+            recursive,
+            include: parse(include)?,
+            exclude: parse(exclude)?,
+        })
+    }
+
+    fn accepts(&self, relative_path: &Path) -> bool {
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        };
+        let matches = |pattern: &Pattern| pattern.matches_path_with(relative_path, options);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// An image of an input directory and the directory where its linearts are written
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InputImage {
+    pub path: PathBuf,
+    pub output_dir: PathBuf,
+}
+
+/// Finds the images of the input directory, sorted by path so that they are started in the same order on every run
+/// The linearts of `directory/chapter_1/page.png` are written in `output_dir/chapter_1/page` (`output_dir/page` without recursion),
+/// and when two images would share their output directory, like `page.png` and `page.jpg`, their extension is added to it: `page_png` and `page_jpg`
+pub fn find_images(
+    directory: impl AsRef<Path>,
+    filter: &InputFilter,
+    output_dir: impl AsRef<Path>,
+) -> Result<Vec<InputImage>> {
+    let directory = directory.as_ref();
+    let mut images = vec![];
+    // the outputs of the previous runs are not inputs when the output directory is inside the input directory
+    let skipped = fs::canonicalize(output_dir.as_ref()).ok();
+    find_images_in(
+        directory,
+        directory,
+        filter,
+        skipped.as_deref(),
+        &mut images,
+    )?;
+    images.sort();
+
+    // the output directories of the subdirectories can also collide with the ones of the images
+    let mut taken: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    for relative_path in &images {
+        for ancestor in relative_path.ancestors().skip(1) {
+            if ancestor != Path::new("") {
+                taken.insert(ancestor.to_owned(), 2);
+            }
+        }
+        *taken.entry(relative_path.with_extension("")).or_default() += 1;
+    }
+    let mut used = BTreeSet::new();
+    let mut inputs = vec![];
+    for relative_path in images {
+        let mut relative_output = relative_path.with_extension("");
+        if taken[&relative_output] > 1 {
+            let extension = relative_path
+                .extension()
+                .and_then(OsStr::to_str)
+                .unwrap_or_default()
+                .to_lowercase();
+            let mut name = relative_output.into_os_string();
+            name.push(format!("_{}", extension));
+            relative_output = PathBuf::from(name);
+            warn!(
+                "{:?} has the same name as another input, its linearts are written in {:?}",
+                relative_path, relative_output
+            );
+        }
+        // the extensions can only differ by their case, like `page.PNG` and `page.png`
+        while !used.insert(relative_output.clone()) {
+            let mut name = relative_output.into_os_string();
+            name.push("_");
+            relative_output = PathBuf::from(name);
+        }
+        inputs.push(InputImage {
+            path: directory.join(&relative_path),
+            output_dir: output_dir.as_ref().join(relative_output),
+        });
+    }
+    Ok(inputs)
+}
+
+/// Adds the images of `current` to `images`, relative to `root`, without going into the `skipped` directory
+fn find_images_in(
+    root: &Path,
+    current: &Path,
+    filter: &InputFilter,
+    skipped: Option<&Path>,
+    images: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in fs::read_dir(current).with_context(|| format!("Cannot read {:?}", current))? {
+        let path = entry?.path();
+        let relative_path = path.strip_prefix(root)?.to_owned();
+        if path.is_dir() {
+            let is_skipped = skipped.is_some_and(|skipped| {
+                fs::canonicalize(&path).is_ok_and(|directory| directory == skipped)
+            });
+            if filter.recursive && !is_skipped {
+                find_images_in(root, &path, filter, skipped, images)?;
+            }
+        } else if path.is_file() && is_image(&path) && filter.accepts(&relative_path) {
+            images.push(relative_path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether the file has the extension of an image, in any case
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|extension| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|image_extension| extension.eq_ignore_ascii_case(image_extension))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colliding_images_get_distinct_output_directories() {
+        let directory =
+            std::env::temp_dir().join(format!("lineart_ify_inputs_{}", std::process::id()));
+        fs::create_dir_all(directory.join("a")).unwrap();
+        for name in ["page.png", "page.PNG", "a.png", "a/b.png"] {
+            fs::write(directory.join(name), b"").unwrap();
+        }
+        let filter = InputFilter::new(true, &[], &[]).unwrap();
+        let output_dir = directory.join("out");
+        let inputs = find_images(&directory, &filter, &output_dir).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(inputs.len(), 4);
+        for (index, input) in inputs.iter().enumerate() {
+            assert!(input.output_dir.starts_with(&output_dir));
+            // an output directory inside another one would mix the outputs of two images
+            for other in &inputs[index + 1..] {
+                assert!(!input.output_dir.starts_with(&other.output_dir));
+                assert!(!other.output_dir.starts_with(&input.output_dir));
+            }
+        }
+        let output_of = |name: &str| {
+            inputs
+                .iter()
+                .find(|input| input.path == directory.join(name))
+                .map(|input| {
+                    input
+                        .output_dir
+                        .strip_prefix(&output_dir)
+                        .unwrap()
+                        .to_owned()
+                })
+                .unwrap()
+        };
+        assert_eq!(output_of("a/b.png"), Path::new("a/b"));
+        assert_eq!(output_of("a.png"), Path::new("a_png"));
+    }
+}
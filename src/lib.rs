@@ -0,0 +1,40 @@
+//! Transform your images into linearts, black or coloured with [`ink::Ink`].
+//!
+//! The linearts can be generated in memory with [`generate_lineart`], or for a whole [`SweepSpec`] of parameters
+//! (the blur radius and the darken level by default) written to disk with [`image_generation::generate_images_and_grid`].
+//!
+//! Your own algorithms can be used everywhere, including on the command line with [`cli::run`], by implementing
+//! [`LineartMethod`] and adding them to a [`MethodRegistry`].
+
+pub mod alpha;
+mod cache;
+mod canny;
+mod centerline;
+pub mod cli;
+pub mod config;
+pub mod despeckle;
+mod edge_tangent_flow;
+pub mod export;
+pub mod fdog;
+pub mod gradient;
+pub mod image_generation;
+pub mod ink;
+pub mod inputs;
+pub mod lineart;
+pub mod manifest;
+pub mod metadata;
+pub mod method;
+pub mod params;
+pub mod pipeline;
+pub mod prefilter;
+pub mod summary;
+pub mod sweep;
+mod trace;
+pub mod watch;
+pub mod xdog;
+
+pub use lineart::MethodParameters;
+pub use method::{LineartMethod, MethodRegistry, ParameterSpec};
+pub use params::{LineartParams, LineartParamsBuilder};
+pub use pipeline::generate_lineart;
+pub use sweep::{SweepAxis, SweepSpec, SweepValue, Variant};
@@ -1,204 +1,343 @@
-use anyhow::{Context, Result};
-use image::Rgba;
+use std::collections::BTreeMap;
+
+use crate::{
+    canny,
+    fdog::{self, FdogParameters},
+    gradient::{self, Gradient, Kernel},
+    method::{LineartMethod, ParameterSpec},
+    xdog::{self, XdogParameters},
+};
+use anyhow::Result;
+use image::{GrayImage, Luma};
 use photon_rs::{
     channels::invert,
-    conv::{gaussian_blur, noise_reduction, sobel_horizontal, sobel_vertical},
+    conv::{gaussian_blur, noise_reduction},
     monochrome::desaturate,
     multiple::blend,
     PhotonImage,
 };
 
-#[derive(Clone, Copy, Debug, clap::ValueEnum)]
-pub(crate) enum Method {
-    Gaussian,
-    Sobel,
+/// The parameters of the methods, each method only reads its own
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct MethodParameters {
+    /// Used by the Sobel, Canny and FDoG methods
+    pub gradient_kernel: Kernel,
+    /// The low and high hysteresis thresholds of the Canny method, in grey levels per pixel
+    pub canny_thresholds: (f32, f32),
+    pub xdog: XdogParameters,
+    pub fdog: FdogParameters,
+    /// The values of the parameters of the methods registered outside of this crate, by parameter name
+    pub custom: BTreeMap<String, f64>,
 }
 
-pub(crate) fn gaussian_blend_dodge(mut image: PhotonImage, blur_radius: i32) -> PhotonImage {
-    desaturate(&mut image);
-    let mut blend_layer = image.clone();
-    invert(&mut blend_layer);
-    gaussian_blur(&mut blend_layer, blur_radius);
-    blend(&mut image, &blend_layer, "dodge");
-    noise_reduction(&mut image);
-    image_color_to_alpha(&mut image, Rgba([255, 255, 255, 255]), 0, 255);
-    image
+impl Default for MethodParameters {
+    fn default() -> Self {
+        MethodParameters {
+            gradient_kernel: Kernel::Sobel,
+            canny_thresholds: (12.5, 25.0),
+            xdog: XdogParameters::default(),
+            fdog: FdogParameters::default(),
+            custom: BTreeMap::new(),
+        }
+    }
 }
 
-fn calculate_global_sobel(image: PhotonImage) -> Result<PhotonImage> {
-    let mut sobel_y = image.clone();
-    let mut sobel_x = image;
-    sobel_horizontal(&mut sobel_x);
-    sobel_vertical(&mut sobel_y);
+impl MethodParameters {
+    /// The value given to a custom parameter, or its default when there is none
+    pub fn custom_value(&self, parameter: &ParameterSpec) -> f64 {
+        self.custom
+            .get(parameter.name)
+            .copied()
+            .unwrap_or(parameter.default)
+    }
+}
 
-    let sob_x_values = sobel_x.get_raw_pixels();
-    let sob_y_values = sobel_y.get_raw_pixels();
+/// Pencil sketch look: the image is blended with its blurred negative with a dodge blend
+pub struct Gaussian;
 
-    let width = sobel_x.get_width();
-    let height = sobel_x.get_height();
+impl LineartMethod for Gaussian {
+    fn name(&self) -> &str {
+        "gaussian"
+    }
 
-    let mut sob_xy_values = vec![];
+    fn description(&self) -> &str {
+        "Pencil sketch, dodge blend of the image with its blurred negative"
+    }
 
-    for i in 0..(sob_x_values.len()) {
-        let kx = *(sob_x_values.get(i).with_context(||
-            format!("No available value in the sobel X component at index {} when there should be a value at this index", i),
-        )?) as u32;
-        let ky = *(sob_y_values.get(i).with_context(||
-            format!("No available value in the sobel Y component at index {} when there should be a value at this index", i),
-        )?) as u32;
-        let kxy_2 = kx * kx + ky * ky; // u8 * u8 is u16 and we sum two so we need u32
-        sob_xy_values.push((kxy_2 as f64).sqrt() as u8);
+    fn apply(
+        &self,
+        image: PhotonImage,
+        blur_radius: i32,
+        _: &MethodParameters,
+    ) -> Result<PhotonImage> {
+        Ok(gaussian_blend_dodge(image, blur_radius))
     }
+}
 
-    let image_sobel = PhotonImage::new(sob_xy_values, width, height);
+/// Same as [`Gaussian`], on the gradient magnitude of the luminance
+pub struct Sobel;
 
-    Ok(image_sobel)
+impl LineartMethod for Sobel {
+    fn name(&self) -> &str {
+        "sobel"
+    }
+
+    fn description(&self) -> &str {
+        "Pencil sketch of the gradient magnitude, tuned with --gradient-kernel"
+    }
+
+    fn apply(
+        &self,
+        image: PhotonImage,
+        blur_radius: i32,
+        parameters: &MethodParameters,
+    ) -> Result<PhotonImage> {
+        Ok(sobel_blend_dodge(
+            image,
+            blur_radius,
+            parameters.gradient_kernel,
+        ))
+    }
 }
 
-pub(crate) fn sobel_blend_dodge(image: PhotonImage, blur_radius: i32) -> PhotonImage {
-    let mut sobel = calculate_global_sobel(image).unwrap();
-    desaturate(&mut sobel);
+/// Crisp one pixel wide lines, see [`canny_lines`]
+pub struct Canny;
+
+impl LineartMethod for Canny {
+    fn name(&self) -> &str {
+        "canny"
+    }
+
+    fn description(&self) -> &str {
+        "Crisp one pixel wide lines from a Canny edge detection, tuned with --canny-*-threshold"
+    }
+
+    fn apply(
+        &self,
+        image: PhotonImage,
+        blur_radius: i32,
+        parameters: &MethodParameters,
+    ) -> Result<PhotonImage> {
+        canny_lines(
+            image,
+            blur_radius,
+            parameters.canny_thresholds.0,
+            parameters.canny_thresholds.1,
+            parameters.gradient_kernel,
+        )
+    }
+}
+
+/// Manga style ink lines, see [`xdog_lines`]
+pub struct Xdog;
+
+impl LineartMethod for Xdog {
+    fn name(&self) -> &str {
+        "xdog"
+    }
+
+    fn description(&self) -> &str {
+        "Manga style ink lines from an Extended Difference of Gaussians, tuned with --xdog-*"
+    }
+
+    fn apply(
+        &self,
+        image: PhotonImage,
+        blur_radius: i32,
+        parameters: &MethodParameters,
+    ) -> Result<PhotonImage> {
+        xdog_lines(image, blur_radius, parameters.xdog)
+    }
+}
+
+/// Continuous lines following the structure of the image, see [`fdog_lines`]
+pub struct Fdog;
+
+impl LineartMethod for Fdog {
+    fn name(&self) -> &str {
+        "fdog"
+    }
+
+    fn description(&self) -> &str {
+        "Continuous lines from a Difference of Gaussians following the edge flow, tuned with --fdog-* and --etf-*"
+    }
+
+    fn apply(
+        &self,
+        image: PhotonImage,
+        blur_radius: i32,
+        parameters: &MethodParameters,
+    ) -> Result<PhotonImage> {
+        fdog_lines(
+            image,
+            blur_radius,
+            parameters.fdog,
+            parameters.gradient_kernel,
+        )
+    }
+}
+
+/// Darkens the lines with a power curve, in a single pass: every colour channel, taken as its 8-bit sRGB value between 0 and 1,
+/// is raised to the power `1 + level`, so a level of 0 leaves the lineart unchanged and any positive level can be used, like 2.5
+/// This curve replaces the repeated multiply blends of the lineart with itself, the images are darker with a higher level
+/// but not the same as the ones given by the blends
+/// The alpha follows the same curve on the transparency: `1 - (1 - alpha)^(1 + level)`
+pub fn darken(lineart: &PhotonImage, level: f32) -> PhotonImage {
+    let exponent = 1_f32 + level.max(0_f32);
+    // the curve only depends on the value of the channel, so it is computed once for the 256 values
+    let colour_curve: [u8; 256] = std::array::from_fn(|value| {
+        ((value as f32 / 255_f32).powf(exponent) * 255_f32).round() as u8
+    });
+    let alpha_curve: [u8; 256] = std::array::from_fn(|alpha| 255 - colour_curve[255 - alpha]);
+    let mut raw_pixels = lineart.get_raw_pixels();
+    for pixel in raw_pixels.chunks_exact_mut(4) {
+        for channel in &mut pixel[0..3] {
+            *channel = colour_curve[*channel as usize];
+        }
+        pixel[3] = alpha_curve[pixel[3] as usize];
+    }
+    PhotonImage::new(raw_pixels, lineart.get_width(), lineart.get_height())
+}
+
+pub fn gaussian_blend_dodge(mut image: PhotonImage, blur_radius: i32) -> PhotonImage {
+    desaturate(&mut image);
+    let mut blend_layer = image.clone();
+    invert(&mut blend_layer);
+    gaussian_blur(&mut blend_layer, blur_radius);
+    blend(&mut image, &blend_layer, "dodge");
+    noise_reduction(&mut image);
+    image
+}
+
+/// Same as the Gaussian method, but on the gradient magnitude of the luminance computed with `kernel`
+pub fn sobel_blend_dodge(image: PhotonImage, blur_radius: i32, kernel: Kernel) -> PhotonImage {
+    let gradient = Gradient::new(&gradient::luminance(&image), kernel);
+    let mut sobel = gradient::float_to_photon(&gradient.normalized_magnitude());
     let mut base_layer = sobel.clone();
     invert(&mut base_layer);
     gaussian_blur(&mut sobel, blur_radius);
     blend(&mut base_layer, &sobel, "dodge");
     noise_reduction(&mut base_layer);
-    image_color_to_alpha(&mut base_layer, Rgba([255, 255, 255, 255]), 0, 255);
     base_layer
 }
 
-fn rgb_distance(color_1: Rgba<u8>, color_2: Rgba<u8>) -> u8 {
-    let mut max_diff = 0;
-    for i in 0..2 {
-        let color_diff = (color_1.0[i] as i16).abs_diff(color_2.0[i] as i16) as u8;
-        if color_diff > max_diff {
-            max_diff = color_diff
+/// Crisp one pixel wide lines, using a Canny edge detection on the image blurred by `blur_radius`
+/// `low_threshold` and `high_threshold` are the hysteresis thresholds applied on the magnitude of the gradient computed with `kernel`,
+/// in grey levels per pixel, they must be positive with `low_threshold` not above `high_threshold`
+pub fn canny_lines(
+    mut image: PhotonImage,
+    blur_radius: i32,
+    low_threshold: f32,
+    high_threshold: f32,
+    kernel: Kernel,
+) -> Result<PhotonImage> {
+    desaturate(&mut image);
+    gaussian_blur(&mut image, blur_radius);
+    let edges = canny::canny_edges(
+        &photon_to_gray(&image),
+        low_threshold,
+        high_threshold,
+        kernel,
+    )?;
+    // the edges are white on black, the lineart is black on white
+    let mut lines = gray_to_photon(&edges);
+    invert(&mut lines);
+    Ok(lines)
+}
+
+/// Manga style ink lines, using an Extended Difference of Gaussians
+/// The standard deviation of the smallest Gaussian is half of `blur_radius`, so that it follows the same sweep as the other methods
+pub fn xdog_lines(
+    mut image: PhotonImage,
+    blur_radius: i32,
+    parameters: XdogParameters,
+) -> Result<PhotonImage> {
+    desaturate(&mut image);
+    let sigma = (blur_radius as f32 / 2_f32).max(0.1);
+    let lines = xdog::xdog(&photon_to_gray(&image), sigma, parameters)?;
+    Ok(gray_to_photon(&lines))
+}
+
+/// Continuous lines following the structure of the image, using a flow-based Difference of Gaussians
+/// The standard deviation of the DoG across the edges is half of `blur_radius`, like for the XDoG method,
+/// and the edge flow is computed from the gradient given by `kernel`
+pub fn fdog_lines(
+    mut image: PhotonImage,
+    blur_radius: i32,
+    parameters: FdogParameters,
+    kernel: Kernel,
+) -> Result<PhotonImage> {
+    desaturate(&mut image);
+    let sigma = (blur_radius as f32 / 2_f32).max(0.1);
+    let lines = fdog::fdog(&photon_to_gray(&image), sigma, parameters, kernel)?;
+    Ok(gray_to_photon(&lines))
+}
+
+/// Takes the red channel of the image as the grey value, the image should already be desaturated
+fn photon_to_gray(image: &PhotonImage) -> GrayImage {
+    let raw_pixels = image.get_raw_pixels();
+    GrayImage::from_fn(image.get_width(), image.get_height(), |x, y| {
+        let index = ((y * image.get_width() + x) * 4) as usize;
+        Luma([raw_pixels[index]])
+    })
+}
+
+/// Builds an opaque RGBA image with R=G=B equal to the grey value
+fn gray_to_photon(image: &GrayImage) -> PhotonImage {
+    let mut raw_pixels = Vec::with_capacity(image.len() * 4);
+    for pixel in image.pixels() {
+        let grey = pixel.0[0];
+        raw_pixels.extend([grey, grey, grey, 255]);
+    }
+    PhotonImage::new(raw_pixels, image.width(), image.height())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 256x1 lineart with every grey value, and an alpha going the other way
+    fn every_value() -> PhotonImage {
+        let raw_pixels = (0..=255_u8)
+            .flat_map(|value| [value, value, value, 255 - value])
+            .collect();
+        PhotonImage::new(raw_pixels, 256, 1)
+    }
+
+    fn pixel(image: &PhotonImage, x: usize) -> [u8; 4] {
+        image.get_raw_pixels()[x * 4..x * 4 + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn darken_raises_the_colours_to_a_power() {
+        let image = every_value();
+        // (128 / 255)^2 * 255 = 64.25
+        assert_eq!(pixel(&darken(&image, 1_f32), 128)[0..3], [64, 64, 64]);
+        // (128 / 255)^1.5 * 255 = 90.69
+        assert_eq!(pixel(&darken(&image, 0.5), 128)[0..3], [91, 91, 91]);
+        // black and white are left as they are
+        for level in [0.5, 1_f32, 3_f32] {
+            let darkened = darken(&image, level);
+            assert_eq!(pixel(&darkened, 0)[0..3], [0, 0, 0]);
+            assert_eq!(pixel(&darkened, 255)[0..3], [255, 255, 255]);
         }
     }
-    max_diff
-}
-
-fn color_to_alpha(
-    color_to_change: Rgba<u8>,
-    compare_to_color: Rgba<u8>,
-    opacity_threshold: u8,
-    transparency_threshold: u8,
-) -> Rgba<u8> {
-    let distance = rgb_distance(color_to_change, compare_to_color);
-    let alpha: u8 = match (distance as f32 - transparency_threshold as f32)
-        / (opacity_threshold as f32 - transparency_threshold as f32)
-    {
-        x if x < 0_f32 => 0,
-        x if (0_f32..=255_f32).contains(&x) => x as u8,
-        x if x > 255_f32 => 255,
-        _ => 255,
-    };
-    let mut new_color = color_to_change;
-    new_color.0[3] = alpha;
-    new_color
-}
-
-pub(crate) fn image_color_to_alpha(
-    image_to_change: &mut PhotonImage,
-    compare_to_color: Rgba<u8>,
-    opacity_threshold: u8,
-    transparency_threshold: u8,
-) {
-    //make chunks here of 4 by 4
-    for pixel in image_to_change.get_raw_pixels().chunks_exact_mut(4) {
-        let alpha_pixel = color_to_alpha(
-            Rgba((*pixel)[0..4].try_into().unwrap()),
-            compare_to_color,
-            opacity_threshold,
-            transparency_threshold,
-        )
-        .0;
-        pixel.copy_from_slice(&alpha_pixel);
-    }
-    // this is only correct for a base ImageBuffer (RgbaImage)
-    // for pixel in image_to_change.pixels_mut() {
-    //     *pixel = color_to_alpha(
-    //         pixel.to_rgba(),
-    //         compare_to_color,
-    //         opacity_threshold,
-    //         transparency_threshold,
-    //     );
-    // }
-}
-
-// /// Changes the midpoint of the grayscale from 122 to the new midpooint
-// /// Tends to darken the dark lines if midpoint is more than 127
-// /// Tends to erase the dark lines if midpoint is less than 127
-// /// What it does is assign the range 0-127 to 0-new_midpoint and 127-255 to new_midpoint-255
-// /// This is done by a linear correlation
-// /// Only the RGB values are changed, the alpha is left untouched
-// /// Note that you can give this function a non-greyscale value, but this function assumes R=G=B and will take the value of the red channel for all the other colour channels
-// pub(crate) fn change_grayscale_range_midpoint(
-//     image: PhotonImage,
-//     new_midpoint: u8,
-// ) -> Result<PhotonImage> {
-//     fn new_low_range(x_value: u8, new_midpoint: u8) -> u8 {
-//         // we make a linear correlation y = ax + b
-//         // using (x1, y1) = (0,0) and (x2, y2) = (127, new_midpoint)
-//         // classic formula gives:
-//         // y = y1 + (y2 - y1)/(x2 - x1)*(x - x1)
-//         assert!(x_value <= new_midpoint);
-//         let x1 = 0;
-//         let y1 = 0;
-//         let x2 = 127;
-//         let y2 = new_midpoint;
-//         let a = ((y2 - y1) as f32) / ((x2 - x1) as f32);
-//         y1 + ((a * ((x2 - x1) as f32)) as u8)
-//     }
-//     fn new_high_range(x_value: u8, new_midpoint: u8) -> u8 {
-//         // we make a linear correlation y = ax + b
-//         // using (x1, y1) = (127, new_midpoint) and (x2, y2) = (255, 255)
-//         // classic formula gives:
-//         // y = y1 + (y2 - y1)/(x2 - x1)*(x - x1)
-//         assert!(x_value >= new_midpoint);
-//         let x1 = 127;
-//         let y1 = new_midpoint;
-//         let x2 = 255;
-//         let y2 = 255;
-//         let a = ((y2 - y1) as f32) / ((x2 - x1) as f32);
-//         y1 + ((a * ((x2 - x1) as f32)) as u8)
-//     }
-//     let width = image.get_width();
-//     let height = image.get_height();
-
-//     let raw_pixels = image.get_raw_pixels();
-//     let mut new_pixels = Vec::with_capacity(raw_pixels.len());
-//     // cannot just image.get_raw_pixels().chunks_exact() because of temporary value dropped while borrowed
-//     let mut pixel_iter = raw_pixels.chunks_exact(4);
-//     // using chunks of 4 because of RGBA format
-//     for pixel in pixel_iter.by_ref() {
-//         let grey_value = *pixel
-//             .first()
-//             .context("The current pixel doesn't have any value inside")?;
-//         let alpha = *pixel
-//             .get(3)
-//             .context("The current pixel doesn't have an alpha value")?;
-//         let new_gray = if grey_value <= new_midpoint {
-//             new_low_range(grey_value, new_midpoint)
-//         } else {
-//             new_high_range(grey_value, new_midpoint)
-//         };
-//         // keep the same alpha
-//         let new_pixel = vec![new_gray, new_gray, new_gray, alpha];
-//         new_pixels.extend(new_pixel);
-//     }
-//     // just in case the size was actually not divisible
-//     new_pixels.extend(pixel_iter.remainder().to_vec());
-
-//     Ok(PhotonImage::new(new_pixels, width, height))
-// }
-
-// /// Repeteadly blend an image with itself with the a multiply blend
-// /// This will darken the lines
-// pub(crate) fn blend_multiply_repeat(image: &mut PhotonImage, repeat_number: u8) {
-//     let original_image = image.clone();
-//     // we could do it faster by using a base 2 decomposition of repeat number (a fast exponentiation but with the images)
-//     for _ in 0..repeat_number {
-//         blend(image, &original_image, "multiply")
-//     }
-// }
+
+    #[test]
+    fn darken_makes_the_lines_more_opaque() {
+        let image = every_value();
+        // the pixel 127 has an alpha of 128: 255 - (127 / 255)^2 * 255 = 191.75
+        assert_eq!(pixel(&darken(&image, 1_f32), 127)[3], 192);
+        assert_eq!(pixel(&darken(&image, 1_f32), 0)[3], 255);
+        assert_eq!(pixel(&darken(&image, 1_f32), 255)[3], 0);
+    }
+
+    #[test]
+    fn darken_level_0_changes_nothing() {
+        let image = every_value();
+        assert_eq!(
+            darken(&image, 0_f32).get_raw_pixels(),
+            image.get_raw_pixels()
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    alpha::AlphaParameters,
+    despeckle::DespeckleParameters,
+    export::ExportParameters,
+    image_generation::build_image_output_path,
+    ink::Ink,
+    lineart::MethodParameters,
+    params::LineartParams,
+    prefilter::PrefilterParameters,
+    sweep::{SweepValue, Variant},
+};
+
+/// The name of the manifest of an image in its output directory, and of the manifest of a directory in the output directory
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Everything generated for an input image, written to `manifest.json` in its output directory
+/// so that the outputs can be found without parsing their names
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub source: PathBuf,
+    pub output_dir: PathBuf,
+    /// The size of the image resized to the target area, which is the size of every lineart
+    pub width: u32,
+    pub height: u32,
+    /// The method used when it is not swept
+    pub method: String,
+    /// The values of every axis of the sweep, in the order of the sweep
+    pub sweep: Map<String, Value>,
+    pub images: Vec<ImageManifest>,
+    /// The summary images and the HTML summary, empty when they could not be generated
+    pub summaries: Vec<PathBuf>,
+    /// How long the image took in this run, the images that were up to date are not generated again
+    pub milliseconds: u64,
+}
+
+/// A lineart of the sweep
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImageManifest {
+    pub name: String,
+    pub path: PathBuf,
+    /// The files of the vector formats
+    pub exports: Vec<PathBuf>,
+    /// The swept values of the lineart, by axis
+    pub values: Map<String, Value>,
+    /// Every parameter used to generate the lineart, swept or not
+    pub parameters: Value,
+    /// `None` when the lineart was up to date and its previous timings are not known
+    pub timings: Option<Timings>,
+    /// Why the lineart could not be generated, its files are missing or outdated then
+    pub error: Option<String>,
+}
+
+/// How long the steps of a lineart took, in milliseconds
+/// The linearts that only differ by their darken level share their method, and by their blur radius too their pre-filter,
+/// the time of a shared step is given for each of them
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Timings {
+    pub prefilter: u64,
+    pub method: u64,
+    /// The darken, the despeckle, the exports, the ink and the paper of this lineart only, and writing its files
+    pub finish: u64,
+}
+
+/// An input image in the manifest of a directory
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InputManifest {
+    pub source: PathBuf,
+    pub output_dir: PathBuf,
+    /// The manifest of the image, `None` when it could not be written
+    pub manifest: Option<PathBuf>,
+    pub milliseconds: u64,
+    pub error: Option<String>,
+}
+
+/// Every input image of a directory, written to `manifest.json` in the output directory
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DirectoryManifest {
+    pub input_directory: PathBuf,
+    pub inputs: Vec<InputManifest>,
+}
+
+impl Manifest {
+    /// The manifest of a previous run, `None` when there is none or it cannot be read
+    pub(crate) fn read(output_dir_for_images: &Path) -> Option<Self> {
+        let text = fs::read_to_string(output_dir_for_images.join(MANIFEST_FILE_NAME)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub(crate) fn write(&self) -> Result<()> {
+        fs::write(
+            self.output_dir.join(MANIFEST_FILE_NAME),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+impl ImageManifest {
+    pub(crate) fn new(
+        variant: &Variant,
+        output_dir_for_images: &Path,
+        timings: Option<Timings>,
+        error: Option<String>,
+    ) -> Result<Self> {
+        let params = &variant.params;
+        let path = PathBuf::from(build_image_output_path(
+            output_dir_for_images,
+            &variant.name,
+        )?);
+        let values = params
+            .sweep
+            .axes()
+            .iter()
+            .zip(&variant.indices)
+            .map(|(axis, &index)| (axis.name().to_owned(), sweep_value(&axis.values()[index])))
+            .collect();
+        Ok(ImageManifest {
+            name: variant.name.clone(),
+            exports: params
+                .export_parameters
+                .formats
+                .iter()
+                .map(|format| format.path(&path))
+                .collect(),
+            path,
+            values,
+            parameters: parameters(variant)?,
+            timings,
+            error,
+        })
+    }
+}
+
+impl DirectoryManifest {
+    pub(crate) fn write(&self, output_dir: &Path) -> Result<()> {
+        fs::create_dir_all(output_dir)?;
+        fs::write(
+            output_dir.join(MANIFEST_FILE_NAME),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// The values of every axis of the sweep of `params`
+pub(crate) fn sweep_values(params: &LineartParams) -> Map<String, Value> {
+    params
+        .sweep
+        .axes()
+        .iter()
+        .map(|axis| {
+            let values = axis.values().iter().map(sweep_value).collect();
+            (axis.name().to_owned(), Value::Array(values))
+        })
+        .collect()
+}
+
+fn sweep_value(value: &SweepValue) -> Value {
+    match value {
+        SweepValue::Number(number) => Value::from(*number),
+        SweepValue::Method(method) => Value::from(method.name()),
+    }
+}
+
+/// The parameters of a lineart, with the names of the fields of the parameters structs
+#[derive(Serialize)]
+struct VariantParameters<'a> {
+    target_size: (u32, u32),
+    method: &'a str,
+    method_parameters: &'a MethodParameters,
+    prefilter: Option<PrefilterParameters>,
+    prefilter_strength: Option<u32>,
+    blur_radius: i32,
+    darken: f32,
+    alpha: &'a AlphaParameters,
+    despeckle: &'a DespeckleParameters,
+    ink: Option<Ink>,
+    export: &'a ExportParameters,
+}
+
+/// Every parameter used to generate the lineart of the variant, swept or not
+pub(crate) fn parameters(variant: &Variant) -> Result<Value> {
+    let params = &variant.params;
+    let parameters = VariantParameters {
+        target_size: params.target_size,
+        method: params.method.name(),
+        method_parameters: &params.method_parameters,
+        prefilter: params.prefilter,
+        prefilter_strength: params.prefilter.map(|_| variant.prefilter_strength),
+        blur_radius: variant.blur_radius,
+        darken: variant.darken,
+        alpha: &params.alpha,
+        despeckle: &params.despeckle,
+        ink: params.ink,
+        export: &params.export_parameters,
+    };
+    // going through the JSON text keeps the shortest representation of the f32 values, 0.1 instead of 0.10000000149011612
+    Ok(serde_json::from_str(&serde_json::to_string(&parameters)?)?)
+}
@@ -0,0 +1,138 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use photon_rs::PhotonImage;
+
+use crate::{manifest, summary, sweep::Variant};
+
+/// The keyword of the name and version of this crate, one of the standard keywords of PNG
+pub const SOFTWARE: &str = "Software";
+/// The keyword of the path of the image the lineart was made from
+pub const SOURCE: &str = "lineart_ify source";
+/// The keyword of the FNV-1a hash of the file the lineart was made from, to check that it is still the same image
+pub const SOURCE_HASH: &str = "lineart_ify source hash";
+/// The keyword of every parameter of the lineart, as JSON, like in the manifest
+pub const PARAMETERS: &str = "lineart_ify parameters";
+/// The keyword of the command line that generates only this lineart, when it was generated from the command line
+pub const COMMAND: &str = "lineart_ify command";
+
+/// The text written in the PNG of the lineart of the variant, by keyword
+pub(crate) fn lineart_metadata(
+    variant: &Variant,
+    base_image_path: &Path,
+    source_hash: &str,
+) -> Result<Vec<(String, String)>> {
+    let mut metadata = vec![
+        (
+            SOFTWARE.to_owned(),
+            format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        ),
+        (
+            SOURCE.to_owned(),
+            base_image_path.to_string_lossy().into_owned(),
+        ),
+        (SOURCE_HASH.to_owned(), source_hash.to_owned()),
+        (
+            PARAMETERS.to_owned(),
+            manifest::parameters(variant)?.to_string(),
+        ),
+    ];
+    let command = summary::variant_command(&variant.params, base_image_path, &variant.indices);
+    if !command.is_empty() {
+        metadata.push((COMMAND.to_owned(), command));
+    }
+    Ok(metadata)
+}
+
+/// Saves the image as an 8 bits RGBA PNG with the text, which is written in `iTXt` chunks since it can have any character
+pub(crate) fn save_png(
+    image: &PhotonImage,
+    path: impl AsRef<Path>,
+    text: &[(String, String)],
+) -> Result<()> {
+    let file = File::create(path.as_ref())
+        .with_context(|| format!("Cannot create {:?}", path.as_ref()))?;
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(file), image.get_width(), image.get_height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, value) in text {
+        encoder.add_itxt_chunk(keyword.clone(), value.clone())?;
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.get_raw_pixels())?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Every text of a PNG by keyword, from its `tEXt`, `zTXt` and `iTXt` chunks
+pub fn read_png_text(path: impl AsRef<Path>) -> Result<Vec<(String, String)>> {
+    let file =
+        File::open(path.as_ref()).with_context(|| format!("Cannot open {:?}", path.as_ref()))?;
+    let reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .with_context(|| format!("{:?} is not a PNG", path.as_ref()))?;
+    let info = reader.info();
+    let mut text = vec![];
+    for chunk in &info.uncompressed_latin1_text {
+        text.push((chunk.keyword.clone(), chunk.text.clone()));
+    }
+    for chunk in &info.compressed_latin1_text {
+        text.push((chunk.keyword.clone(), chunk.get_text()?));
+    }
+    for chunk in &info.utf8_text {
+        text.push((chunk.keyword.clone(), chunk.get_text()?));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_and_pixels_survive_a_round_trip() {
+        let directory =
+            std::env::temp_dir().join(format!("lineart_ify_metadata_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("lineart.png");
+        let pixels = vec![0, 0, 0, 255, 10, 20, 30, 128];
+        let image = PhotonImage::new(pixels.clone(), 2, 1);
+        let text = vec![
+            (SOFTWARE.to_owned(), String::from("lineart_ify 1.0")),
+            // iTXt keeps the characters that are not Latin-1
+            (
+                COMMAND.to_owned(),
+                String::from("-i 線画 é.png --paper-color '#ffeedd'"),
+            ),
+        ];
+        save_png(&image, &path, &text).unwrap();
+        let read_text = read_png_text(&path).unwrap();
+        let read_pixels = image::open(&path).unwrap().to_rgba8().into_raw();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(read_text, text);
+        assert_eq!(read_pixels, pixels);
+    }
+
+    #[test]
+    fn other_files_are_not_pngs() {
+        let directory = std::env::temp_dir().join(format!(
+            "lineart_ify_metadata_not_png_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("notes.png");
+        std::fs::write(&path, b"not a png").unwrap();
+        let not_png = read_png_text(&path);
+        let missing = read_png_text(directory.join("missing.png"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(not_png.is_err());
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::{bail, Result};
+use photon_rs::PhotonImage;
+
+use crate::lineart::{Canny, Fdog, Gaussian, MethodParameters, Sobel, Xdog};
+
+/// A numeric parameter read by a method, used to list the methods and to check the values given to them
+#[derive(Clone, Copy, Debug)]
+pub struct ParameterSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The value used when none is given
+    pub default: f64,
+}
+
+/// An algorithm turning an image into a lineart
+/// Implement it and add it to a [`MethodRegistry`] to use your own algorithm with the whole pipeline and the command line
+pub trait LineartMethod: Send + Sync {
+    /// The name used to select the method, on the command line with `--method`
+    fn name(&self) -> &str;
+
+    /// A one line description, shown by `--list-methods`
+    fn description(&self) -> &str;
+
+    /// The parameters read by the method from [`MethodParameters::custom`], given with `--method-parameter` on the command line
+    /// The built-in methods have dedicated fields in [`MethodParameters`] instead
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        vec![]
+    }
+
+    /// Generates the lineart of the image, dark lines on an opaque background
+    /// The background is white for the built-in methods, it is made transparent afterwards (see [`AlphaParameters`](crate::alpha::AlphaParameters))
+    /// `blur_radius` is the value swept by the image generation, each method is free to interpret it
+    /// An error is returned when the parameters read by the method are invalid
+    fn apply(
+        &self,
+        image: PhotonImage,
+        blur_radius: i32,
+        parameters: &MethodParameters,
+    ) -> Result<PhotonImage>;
+}
+
+impl fmt::Debug for dyn LineartMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LineartMethod").field(&self.name()).finish()
+    }
+}
+
+/// The methods that can be selected by name, in the order they were registered
+#[derive(Clone, Default)]
+pub struct MethodRegistry {
+    methods: Vec<Arc<dyn LineartMethod>>,
+}
+
+impl MethodRegistry {
+    /// An empty registry, see [`MethodRegistry::builtin`] to start with the methods of this crate
+    pub fn new() -> Self {
+        MethodRegistry::default()
+    }
+
+    /// A registry with all the methods of this crate: gaussian, sobel, canny, xdog and fdog
+    pub fn builtin() -> Self {
+        MethodRegistry {
+            methods: vec![
+                Arc::new(Gaussian),
+                Arc::new(Sobel),
+                Arc::new(Canny),
+                Arc::new(Xdog),
+                Arc::new(Fdog),
+            ],
+        }
+    }
+
+    /// Adds a method to the registry, its name must not already be used
+    pub fn register(&mut self, method: impl LineartMethod + 'static) -> Result<()> {
+        if self.get(method.name()).is_some() {
+            bail!("A method named {} is already registered", method.name());
+        }
+        self.methods.push(Arc::new(method));
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn LineartMethod>> {
+        self.methods
+            .iter()
+            .find(|method| method.name() == name)
+            .cloned()
+    }
+
+    pub fn methods(&self) -> impl Iterator<Item = &Arc<dyn LineartMethod>> {
+        self.methods.iter()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.methods
+            .iter()
+            .map(|method| method.name().to_owned())
+            .collect()
+    }
+}
+
+impl fmt::Debug for MethodRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.methods.iter()).finish()
+    }
+}
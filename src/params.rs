@@ -0,0 +1,419 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    alpha::AlphaParameters,
+    canny,
+    despeckle::DespeckleParameters,
+    export::ExportParameters,
+    fdog,
+    ink::Ink,
+    lineart::{Gaussian, MethodParameters},
+    method::LineartMethod,
+    prefilter::PrefilterParameters,
+    summary::SummaryParameters,
+    sweep::{self, SweepAxis, SweepSpec, SweepValue, Variant},
+    xdog,
+};
+
+/// The pre-filter strengths, blur radii and darken levels given by a start, a step and a number of values,
+/// they are swept unless an axis of the same name is given to the builder
+#[derive(Clone, Copy, Debug)]
+struct SweepRanges {
+    min_prefilter_strength: u32,
+    prefilter_step: u32,
+    prefilter_number: u8,
+    min_blur_radius: i32,
+    blur_step: i32,
+    blur_number: u8,
+    min_darken_number: f32,
+    darken_step: f32,
+    darken_number: u8,
+}
+
+impl Default for SweepRanges {
+    fn default() -> Self {
+        SweepRanges {
+            min_prefilter_strength: 2,
+            prefilter_step: 1,
+            prefilter_number: 1,
+            min_blur_radius: 3,
+            blur_step: 1,
+            blur_number: 5,
+            min_darken_number: 2.0,
+            darken_step: 1.0,
+            darken_number: 4,
+        }
+    }
+}
+
+impl SweepRanges {
+    /// The axes of the ranges, the pre-filter strength is only swept with a pre-filter
+    fn axes(&self, with_prefilter: bool) -> Result<Vec<SweepAxis>> {
+        if self.prefilter_number == 0 {
+            bail!("prefilter_number must be at least 1");
+        }
+        if self.blur_number == 0 {
+            bail!("blur_number must be at least 1");
+        }
+        if self.darken_number == 0 {
+            bail!("darken_number must be at least 1");
+        }
+        let max_blur_index = self.blur_number as i32 - 1;
+        let last_blur_radius = max_blur_index
+            .checked_mul(self.blur_step)
+            .and_then(|offset| self.min_blur_radius.checked_add(offset))
+            .context("The blur radii of the sweep are too big")?;
+        if self.min_blur_radius < 0 || last_blur_radius < 0 {
+            bail!(
+                "The blur radii must not be negative, the sweep goes from {} to {}",
+                self.min_blur_radius,
+                last_blur_radius
+            );
+        }
+        (self.prefilter_number as u32 - 1)
+            .checked_mul(self.prefilter_step)
+            .and_then(|offset| self.min_prefilter_strength.checked_add(offset))
+            .context("The pre-filter strengths of the sweep are too big")?;
+
+        let mut axes = vec![];
+        if with_prefilter {
+            let strengths = (0..self.prefilter_number)
+                .map(|index| {
+                    let strength = self.min_prefilter_strength + index as u32 * self.prefilter_step;
+                    SweepValue::Number(strength as f64)
+                })
+                .collect();
+            axes.push(SweepAxis::new(sweep::PREFILTER, strengths)?);
+        }
+        let blur_radii = (0..self.blur_number)
+            .map(|index| {
+                let blur_radius = self.min_blur_radius + index as i32 * self.blur_step;
+                SweepValue::Number(blur_radius as f64)
+            })
+            .collect();
+        axes.push(SweepAxis::new(sweep::BLUR, blur_radii)?);
+        // the darken levels are rounded to the thousandth, so that `0.1 * 3` gives `0.3` in the file names
+        let darkens = (0..self.darken_number)
+            .map(|index| {
+                let darken = self.min_darken_number as f64 + index as f64 * self.darken_step as f64;
+                SweepValue::Number((darken * 1000_f64).round() / 1000_f64)
+            })
+            .collect();
+        axes.push(SweepAxis::new(sweep::DARKEN, darkens)?);
+        Ok(axes)
+    }
+}
+
+/// Everything needed to generate the linearts of an image, apart from the input and output paths
+#[derive(Clone, Debug)]
+pub struct LineartParams {
+    /// The images are resized to get an area of `target_size.0 * target_size.1`, keeping their ratio
+    pub target_size: (u32, u32),
+    /// The parameters that change between the images, the other fields are the values of the parameters that are not swept
+    pub sweep: SweepSpec,
+    pub method: Arc<dyn LineartMethod>,
+    pub method_parameters: MethodParameters,
+    /// The filter applied to the image before the method, with the strengths of the sweep, none when it is `None`
+    pub prefilter: Option<PrefilterParameters>,
+    pub alpha: AlphaParameters,
+    /// The speckles removed and the holes filled after the lines are darkened, nothing is changed by default
+    pub despeckle: DespeckleParameters,
+    /// The colour of the lines, they keep the colour given by the method when it is `None`
+    pub ink: Option<Ink>,
+    pub export_parameters: ExportParameters,
+    pub summary: SummaryParameters,
+    /// Generates every image again, even when its outputs are up to date with the input and the parameters
+    pub force: bool,
+}
+
+impl LineartParams {
+    /// Starts from the same defaults as the command line
+    pub fn builder() -> LineartParamsBuilder {
+        LineartParamsBuilder::default()
+    }
+
+    /// Every image of the sweep, the last axis changes first
+    pub fn variants(&self) -> Result<Vec<Variant>> {
+        self.sweep
+            .combinations()
+            .into_iter()
+            .map(|indices| Variant::new(self, indices))
+            .collect()
+    }
+
+    /// Checks the parameters of the methods, of the alpha, of the pre-filter, of the despeckle and of the export,
+    /// for the parameters of one image, the swept values are checked for every variant when the parameters are built
+    pub(crate) fn validate(&self) -> Result<()> {
+        let (low_threshold, high_threshold) = self.method_parameters.canny_thresholds;
+        canny::check_thresholds(low_threshold, high_threshold)?;
+        xdog::check_parameters(&self.method_parameters.xdog)?;
+        fdog::check_parameters(&self.method_parameters.fdog)?;
+        if self.alpha.transparency_threshold >= self.alpha.opacity_threshold {
+            bail!(
+                "The transparency threshold ({}) must be lower than the opacity threshold ({})",
+                self.alpha.transparency_threshold,
+                self.alpha.opacity_threshold
+            );
+        }
+        if let Some(prefilter) = &self.prefilter {
+            for (name, value) in [
+                ("bilateral sigma color", prefilter.bilateral_sigma_color),
+                ("diffusion kappa", prefilter.diffusion_kappa),
+            ] {
+                if !value.is_finite() || value <= 0_f32 {
+                    bail!("The {} must be a positive number, got {}", name, value);
+                }
+            }
+        }
+        let min_length = self.despeckle.min_length;
+        if !min_length.is_finite() || min_length < 0_f32 {
+            bail!(
+                "The minimum length of the strokes must be a positive number, got {}",
+                min_length
+            );
+        }
+        let tolerance = self.export_parameters.tolerance;
+        if tolerance.is_nan() || tolerance < 0_f64 {
+            bail!(
+                "The trace tolerance must be a positive number, got {}",
+                tolerance
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Builds [`LineartParams`], the values are checked by [`LineartParamsBuilder::build`]
+#[derive(Clone, Debug)]
+pub struct LineartParamsBuilder {
+    target_size: (u32, u32),
+    ranges: SweepRanges,
+    axes: Vec<SweepAxis>,
+    grid_axes: (String, String),
+    method: Arc<dyn LineartMethod>,
+    method_parameters: MethodParameters,
+    prefilter: Option<PrefilterParameters>,
+    alpha: AlphaParameters,
+    despeckle: DespeckleParameters,
+    ink: Option<Ink>,
+    export_parameters: ExportParameters,
+    summary: SummaryParameters,
+    force: bool,
+}
+
+impl Default for LineartParamsBuilder {
+    fn default() -> Self {
+        LineartParamsBuilder {
+            target_size: (500, 600),
+            ranges: SweepRanges::default(),
+            axes: vec![],
+            grid_axes: (sweep::BLUR.to_owned(), sweep::DARKEN.to_owned()),
+            method: Arc::new(Gaussian),
+            method_parameters: MethodParameters::default(),
+            prefilter: None,
+            alpha: AlphaParameters::default(),
+            despeckle: DespeckleParameters::default(),
+            ink: None,
+            export_parameters: ExportParameters::default(),
+            summary: SummaryParameters::default(),
+            force: false,
+        }
+    }
+}
+
+impl LineartParamsBuilder {
+    pub fn target_size(mut self, width: u32, height: u32) -> Self {
+        self.target_size = (width, height);
+        self
+    }
+
+    pub fn min_prefilter_strength(mut self, min_prefilter_strength: u32) -> Self {
+        self.ranges.min_prefilter_strength = min_prefilter_strength;
+        self
+    }
+
+    pub fn prefilter_step(mut self, prefilter_step: u32) -> Self {
+        self.ranges.prefilter_step = prefilter_step;
+        self
+    }
+
+    pub fn prefilter_number(mut self, prefilter_number: u8) -> Self {
+        self.ranges.prefilter_number = prefilter_number;
+        self
+    }
+
+    pub fn min_blur_radius(mut self, min_blur_radius: i32) -> Self {
+        self.ranges.min_blur_radius = min_blur_radius;
+        self
+    }
+
+    pub fn blur_step(mut self, blur_step: i32) -> Self {
+        self.ranges.blur_step = blur_step;
+        self
+    }
+
+    pub fn blur_number(mut self, blur_number: u8) -> Self {
+        self.ranges.blur_number = blur_number;
+        self
+    }
+
+    pub fn min_darken_number(mut self, min_darken_number: f32) -> Self {
+        self.ranges.min_darken_number = min_darken_number;
+        self
+    }
+
+    pub fn darken_step(mut self, darken_step: f32) -> Self {
+        self.ranges.darken_step = darken_step;
+        self
+    }
+
+    pub fn darken_number(mut self, darken_number: u8) -> Self {
+        self.ranges.darken_number = darken_number;
+        self
+    }
+
+    /// Sweeps a parameter, replacing the range of the blur radii, darken levels or pre-filter strengths of the same name
+    /// The axes that are not ranges come first, in the order they were added
+    pub fn sweep_axis(mut self, axis: SweepAxis) -> Self {
+        self.axes.push(axis);
+        self
+    }
+
+    /// The axes drawn as the rows and the columns of the summary images, the blur radius and the darken level by default
+    pub fn grid_axes(mut self, rows: &str, columns: &str) -> Self {
+        self.grid_axes = (rows.to_owned(), columns.to_owned());
+        self
+    }
+
+    /// The methods can be found by name in a [`MethodRegistry`](crate::method::MethodRegistry)
+    pub fn method(mut self, method: Arc<dyn LineartMethod>) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn method_parameters(mut self, method_parameters: MethodParameters) -> Self {
+        self.method_parameters = method_parameters;
+        self
+    }
+
+    pub fn prefilter(mut self, prefilter: Option<PrefilterParameters>) -> Self {
+        self.prefilter = prefilter;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: AlphaParameters) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn despeckle(mut self, despeckle: DespeckleParameters) -> Self {
+        self.despeckle = despeckle;
+        self
+    }
+
+    pub fn ink(mut self, ink: Option<Ink>) -> Self {
+        self.ink = ink;
+        self
+    }
+
+    pub fn export_parameters(mut self, export_parameters: ExportParameters) -> Self {
+        self.export_parameters = export_parameters;
+        self
+    }
+
+    pub fn summary(mut self, summary: SummaryParameters) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Checks that the sweep doesn't overflow, that every image has at least one pixel and valid parameters
+    /// and that the custom parameters are all read by a method of the sweep
+    pub fn build(self) -> Result<LineartParams> {
+        if self.target_size.0 == 0 || self.target_size.1 == 0 {
+            bail!(
+                "The target size must not be empty, got {}x{}",
+                self.target_size.0,
+                self.target_size.1
+            );
+        }
+        let mut ranges = self.ranges.axes(self.prefilter.is_some())?;
+        let mut axes = vec![];
+        for axis in self.axes {
+            if axis.name() == sweep::PREFILTER && self.prefilter.is_none() {
+                bail!("The pre-filter strength can only be swept with a pre-filter");
+            }
+            match ranges.iter_mut().find(|range| range.name() == axis.name()) {
+                Some(range) => *range = axis,
+                None => axes.push(axis),
+            }
+        }
+        axes.append(&mut ranges);
+
+        let mut methods = vec![self.method.clone()];
+        for axis in &axes {
+            if axis.name() == sweep::METHOD {
+                for value in axis.values() {
+                    if let SweepValue::Method(method) = value {
+                        methods.push(method.clone());
+                    }
+                }
+            }
+        }
+        let is_method_parameter = |name: &str| {
+            methods.iter().any(|method| {
+                method
+                    .parameters()
+                    .iter()
+                    .any(|parameter| parameter.name == name)
+            })
+        };
+        for name in self.method_parameters.custom.keys() {
+            if !is_method_parameter(name) {
+                bail!(
+                    "The method {} has no parameter named {}",
+                    self.method.name(),
+                    name
+                );
+            }
+        }
+        let params = LineartParams {
+            target_size: self.target_size,
+            sweep: SweepSpec::new(axes, &self.grid_axes.0, &self.grid_axes.1)?,
+            method: self.method,
+            method_parameters: self.method_parameters,
+            prefilter: self.prefilter,
+            alpha: self.alpha,
+            despeckle: self.despeckle,
+            ink: self.ink,
+            export_parameters: self.export_parameters,
+            summary: self.summary,
+            force: self.force,
+        };
+        for variant in params.variants()? {
+            if variant.blur_radius < 0 {
+                bail!(
+                    "The blur radii must not be negative, got {}",
+                    variant.blur_radius
+                );
+            }
+            if !variant.darken.is_finite() || variant.darken < 0_f32 {
+                bail!(
+                    "The darken levels must be positive numbers, got {}",
+                    variant.darken
+                );
+            }
+            variant
+                .params
+                .validate()
+                .with_context(|| format!("Invalid parameters for {}", variant.name))?;
+        }
+        Ok(params)
+    }
+}
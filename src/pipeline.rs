@@ -0,0 +1,72 @@
+use anyhow::{bail, Result};
+use image::{DynamicImage, RgbaImage};
+use photon_rs::PhotonImage;
+
+use log::debug;
+
+use crate::{alpha, despeckle, ink, lineart, params::LineartParams, prefilter};
+
+/// Generates one lineart of an image in memory, without reading or writing any file
+/// The lineart has the same size as the image, it is made with the method, the pre-filter, the alpha, the despeckle and the ink of `params`
+/// The sweep of `params` is not used, the other swept values are applied by giving the params of a [`Variant`](crate::sweep::Variant)
+/// with its `prefilter_strength`, `blur_radius` and `darken`, but any value can be used
+/// `prefilter_strength` is ignored when `params` has no pre-filter and `darken` is the darken level of the lines, see [`lineart::darken`]
+/// The parameters are checked like in [`LineartParamsBuilder::build`](crate::params::LineartParamsBuilder::build),
+/// since the fields of `params` can be changed after it was built
+pub fn generate_lineart(
+    image: &DynamicImage,
+    params: &LineartParams,
+    prefilter_strength: u32,
+    blur_radius: i32,
+    darken: f32,
+) -> Result<RgbaImage> {
+    if blur_radius < 0 {
+        bail!("The blur radius must not be negative, got {}", blur_radius);
+    }
+    if !darken.is_finite() || darken < 0_f32 {
+        bail!("The darken level must be a positive number, got {}", darken);
+    }
+    params.validate()?;
+    let source = match &params.prefilter {
+        Some(prefilter) => {
+            prefilter::apply_prefilter(&to_photon(image), prefilter_strength, prefilter)
+        }
+        None => to_photon(image),
+    };
+    let lineart = params
+        .method
+        .apply(source.clone(), blur_radius, &params.method_parameters)?;
+    let lineart = alpha::image_color_to_alpha(&lineart, &params.alpha);
+    let lineart = lineart::darken(&lineart, darken);
+    let (lineart, report) = despeckle::despeckle(&lineart, &params.despeckle);
+    if params.despeckle.is_enabled() {
+        debug!(
+            "Removed {} pixels of speckles, filled {} pixels of holes",
+            report.removed_pixels, report.filled_pixels
+        );
+    }
+    let lineart = match params.ink {
+        Some(line_ink) => ink::apply_ink(&lineart, line_ink, &source),
+        None => lineart,
+    };
+    Ok(match params.alpha.paper {
+        Some(paper) => from_photon(alpha::image_on_paper(&lineart, paper)),
+        None => from_photon(lineart),
+    })
+}
+
+/// Converts an image to the format used by photon
+pub fn to_photon(image: &DynamicImage) -> PhotonImage {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    PhotonImage::new(rgba.into_raw(), width, height)
+}
+
+/// Converts an image from the format used by photon
+pub fn from_photon(image: PhotonImage) -> RgbaImage {
+    let width = image.get_width();
+    let height = image.get_height();
+    // photon always stores 4 bytes per pixel, so the buffer has the right size
+    RgbaImage::from_raw(width, height, image.get_raw_pixels())
+        .unwrap_or_else(|| RgbaImage::new(width, height))
+}
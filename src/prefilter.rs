@@ -0,0 +1,274 @@
+use image::RgbaImage;
+use imageproc::filter::median_filter;
+use photon_rs::PhotonImage;
+
+/// How much the anisotropic diffusion changes the image at each iteration, 0.25 is the highest stable value
+const DIFFUSION_RATE: f32 = 0.2;
+/// The number of iterations of the anisotropic diffusion for each level of strength
+const DIFFUSION_ITERATIONS_PER_STRENGTH: u32 = 5;
+
+/// An edge-preserving filter applied to the image before the method, to remove the noise and the textures
+/// that would otherwise become speckles in the lineart
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Prefilter {
+    /// Averages the pixels that are both close and of a similar colour, the strength is the radius of the window
+    Bilateral,
+    /// Perona-Malik diffusion, smooths the flat areas but not across the edges, the strength times 5 is the number of iterations
+    Anisotropic,
+    /// Takes the median of each channel in a square window, the strength is the radius of the window
+    Median,
+    /// Takes the mean of the most uniform of the four quadrants around each pixel, gives a painted look, the strength is the radius
+    Kuwahara,
+}
+
+/// The pre-filter applied before the method and its settings, the strength is swept by the image generation
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct PrefilterParameters {
+    pub filter: Prefilter,
+    /// The standard deviation of the colour difference for the bilateral filter, between 0 and 255
+    /// The higher it is, the more the edges are smoothed
+    pub bilateral_sigma_color: f32,
+    /// The gradient (between 0 and 255) under which the anisotropic diffusion smooths the image
+    /// The higher it is, the more the edges are smoothed
+    pub diffusion_kappa: f32,
+}
+
+impl Default for PrefilterParameters {
+    fn default() -> Self {
+        PrefilterParameters {
+            filter: Prefilter::Bilateral,
+            bilateral_sigma_color: 30.0,
+            diffusion_kappa: 20.0,
+        }
+    }
+}
+
+/// Filters the colour channels of the image, the alpha channel is kept, a strength of 0 returns the image as it is
+pub fn apply_prefilter(
+    image: &PhotonImage,
+    strength: u32,
+    parameters: &PrefilterParameters,
+) -> PhotonImage {
+    if strength == 0 {
+        return image.clone();
+    }
+    let width = image.get_width();
+    let height = image.get_height();
+    let raw_pixels = image.get_raw_pixels();
+    let filtered = match parameters.filter {
+        Prefilter::Bilateral => bilateral(
+            &raw_pixels,
+            width,
+            height,
+            strength,
+            parameters.bilateral_sigma_color,
+        ),
+        Prefilter::Anisotropic => anisotropic_diffusion(
+            &raw_pixels,
+            width,
+            height,
+            strength * DIFFUSION_ITERATIONS_PER_STRENGTH,
+            parameters.diffusion_kappa,
+        ),
+        Prefilter::Median => match RgbaImage::from_raw(width, height, raw_pixels.clone()) {
+            Some(rgba) => {
+                let mut filtered = median_filter(&rgba, strength, strength).into_raw();
+                // the median of the alpha channel would erode the transparent borders
+                for (pixel, original) in
+                    filtered.chunks_exact_mut(4).zip(raw_pixels.chunks_exact(4))
+                {
+                    pixel[3] = original[3];
+                }
+                filtered
+            }
+            None => raw_pixels,
+        },
+        Prefilter::Kuwahara => kuwahara(&raw_pixels, width, height, strength),
+    };
+    PhotonImage::new(filtered, width, height)
+}
+
+fn bilateral(raw_pixels: &[u8], width: u32, height: u32, radius: u32, sigma_color: f32) -> Vec<u8> {
+    let (width, height, radius) = (width as i64, height as i64, radius as i64);
+    let sigma_spatial = (radius as f32 / 2_f32).max(0.5);
+    let spatial_weights: Vec<f32> = (-radius..=radius)
+        .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| {
+            (-((dx * dx + dy * dy) as f32) / (2_f32 * sigma_spatial * sigma_spatial)).exp()
+        })
+        .collect();
+    let sigma_color = sigma_color.max(f32::EPSILON);
+    let color_weight = |squared_distance: i32| {
+        (-(squared_distance as f32) / (2_f32 * sigma_color * sigma_color)).exp()
+    };
+
+    let mut filtered = raw_pixels.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let center = ((y * width + x) * 4) as usize;
+            let center_pixel = &raw_pixels[center..center + 3];
+            let mut sums = [0_f32; 3];
+            let mut total_weight = 0_f32;
+            for dy in -radius..=radius {
+                let neighbour_y = y + dy;
+                if neighbour_y < 0 || neighbour_y >= height {
+                    continue;
+                }
+                for dx in -radius..=radius {
+                    let neighbour_x = x + dx;
+                    if neighbour_x < 0 || neighbour_x >= width {
+                        continue;
+                    }
+                    let neighbour = ((neighbour_y * width + neighbour_x) * 4) as usize;
+                    let neighbour_pixel = &raw_pixels[neighbour..neighbour + 3];
+                    let squared_distance: i32 = center_pixel
+                        .iter()
+                        .zip(neighbour_pixel)
+                        .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+                        .sum();
+                    let spatial_index = ((dy + radius) * (2 * radius + 1) + dx + radius) as usize;
+                    let weight = spatial_weights[spatial_index] * color_weight(squared_distance);
+                    for (sum, &value) in sums.iter_mut().zip(neighbour_pixel) {
+                        *sum += weight * value as f32;
+                    }
+                    total_weight += weight;
+                }
+            }
+            // the center pixel always has a weight of 1, so the total is never 0
+            for (channel, sum) in filtered[center..center + 3].iter_mut().zip(sums) {
+                *channel = (sum / total_weight).round().clamp(0_f32, 255_f32) as u8;
+            }
+        }
+    }
+    filtered
+}
+
+fn anisotropic_diffusion(
+    raw_pixels: &[u8],
+    width: u32,
+    height: u32,
+    iterations: u32,
+    kappa: f32,
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let kappa = kappa.max(f32::EPSILON);
+    // Perona-Malik conductance, close to 1 in the flat areas and close to 0 on the edges
+    let conductance = |gradient: f32| (-(gradient / kappa).powi(2)).exp();
+    let mut channels: Vec<Vec<f32>> = (0..3)
+        .map(|channel| {
+            raw_pixels
+                .chunks_exact(4)
+                .map(|pixel| pixel[channel] as f32)
+                .collect()
+        })
+        .collect();
+    for _ in 0..iterations {
+        for channel in &mut channels {
+            let previous = channel.clone();
+            for y in 0..height {
+                for x in 0..width {
+                    let index = y * width + x;
+                    let value = previous[index];
+                    // the borders have no flow out of the image
+                    let neighbours = [
+                        (x > 0).then(|| previous[index - 1]),
+                        (x + 1 < width).then(|| previous[index + 1]),
+                        (y > 0).then(|| previous[index - width]),
+                        (y + 1 < height).then(|| previous[index + width]),
+                    ];
+                    let flow: f32 = neighbours
+                        .into_iter()
+                        .flatten()
+                        .map(|neighbour| {
+                            let gradient = neighbour - value;
+                            conductance(gradient.abs()) * gradient
+                        })
+                        .sum();
+                    channel[index] = value + DIFFUSION_RATE * flow;
+                }
+            }
+        }
+    }
+    let mut filtered = raw_pixels.to_vec();
+    for (index, pixel) in filtered.chunks_exact_mut(4).enumerate() {
+        for (channel, values) in pixel[0..3].iter_mut().zip(&channels) {
+            *channel = values[index].round().clamp(0_f32, 255_f32) as u8;
+        }
+    }
+    filtered
+}
+
+fn kuwahara(raw_pixels: &[u8], width: u32, height: u32, radius: u32) -> Vec<u8> {
+    let (width, height, radius) = (width as usize, height as usize, radius as usize);
+    // summed area tables of the three channels and of the squared luminance, with a row and a column of zeros
+    // so that the sum over any rectangle takes four lookups
+    let table_width = width + 1;
+    let mut sums = vec![[0_f64; 5]; table_width * (height + 1)];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = &raw_pixels[(y * width + x) * 4..(y * width + x) * 4 + 3];
+            let luminance =
+                0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64;
+            let values = [
+                pixel[0] as f64,
+                pixel[1] as f64,
+                pixel[2] as f64,
+                luminance,
+                luminance * luminance,
+            ];
+            let above = sums[y * table_width + x + 1];
+            let left = sums[(y + 1) * table_width + x];
+            let above_left = sums[y * table_width + x];
+            sums[(y + 1) * table_width + x + 1] =
+                std::array::from_fn(|i| values[i] + above[i] + left[i] - above_left[i]);
+        }
+    }
+    // the sums over the pixels from (x0, y0) included to (x1, y1) excluded
+    let rectangle_sums = |x0: usize, y0: usize, x1: usize, y1: usize| -> [f64; 5] {
+        let bottom_right = sums[y1 * table_width + x1];
+        let top_right = sums[y0 * table_width + x1];
+        let bottom_left = sums[y1 * table_width + x0];
+        let top_left = sums[y0 * table_width + x0];
+        std::array::from_fn(|i| bottom_right[i] - top_right[i] - bottom_left[i] + top_left[i])
+    };
+
+    let mut filtered = raw_pixels.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let left = x.saturating_sub(radius);
+            let top = y.saturating_sub(radius);
+            let right = (x + radius + 1).min(width);
+            let bottom = (y + radius + 1).min(height);
+            let quadrants = [
+                (left, top, x + 1, y + 1),
+                (x, top, right, y + 1),
+                (left, y, x + 1, bottom),
+                (x, y, right, bottom),
+            ];
+            let mut best_variance = f64::INFINITY;
+            let mut best_mean = [0_f64; 3];
+            for (x0, y0, x1, y1) in quadrants {
+                let count = ((x1 - x0) * (y1 - y0)) as f64;
+                let quadrant = rectangle_sums(x0, y0, x1, y1);
+                let mean_luminance = quadrant[3] / count;
+                let variance = quadrant[4] / count - mean_luminance * mean_luminance;
+                if variance < best_variance {
+                    best_variance = variance;
+                    best_mean = [
+                        quadrant[0] / count,
+                        quadrant[1] / count,
+                        quadrant[2] / count,
+                    ];
+                }
+            }
+            let index = (y * width + x) * 4;
+            for (channel, mean) in filtered[index..index + 3].iter_mut().zip(best_mean) {
+                *channel = mean.round().clamp(0_f64, 255_f64) as u8;
+            }
+        }
+    }
+    filtered
+}
@@ -0,0 +1,358 @@
+use std::{
+    fmt::{self, Write},
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use image::{ImageFormat, Rgba, RgbaImage};
+use log::info;
+use photon_rs::PhotonImage;
+
+use crate::{
+    alpha::Color,
+    image_generation::{axis_title, build_image_output_path},
+    params::LineartParams,
+    pipeline,
+};
+
+/// How the summaries of the sweep are made
+#[derive(Clone, Debug, Default)]
+pub struct SummaryParameters {
+    /// The command line that generated the images, without its input and its sweep
+    /// The HTML summary completes it with the input and the swept values of each image, it shows no command when it is empty
+    pub command: Vec<String>,
+    /// What is drawn behind the images in the summary images
+    pub background: GridBackground,
+    /// The maximum width and height of the summary images, their images are downscaled to fit
+    pub max_size: Option<u32>,
+    /// The font of the labels of the summary images, the embedded font is used when it is not given or cannot be read
+    pub font: Option<PathBuf>,
+    /// Whether the source image is drawn above the grids, as a reference to compare the linearts with
+    pub include_source: bool,
+}
+
+/// The size in pixels of the squares of [`GridBackground::Checkerboard`]
+const CHECKER_SIZE: u32 = 8;
+
+/// What is drawn behind the images of the summary images, written on the command line and in the configuration files as:
+/// - `checkerboard` for grey and white squares, like the image editors show transparency
+/// - `#rrggbb` for a solid colour
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum GridBackground {
+    Checkerboard,
+    Color(Color),
+}
+
+impl Default for GridBackground {
+    fn default() -> Self {
+        GridBackground::Color(Color::WHITE)
+    }
+}
+
+impl GridBackground {
+    /// Fills a rectangle of the image with the background
+    pub(crate) fn fill(&self, image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32) {
+        for pixel_y in y..(y + height).min(image.height()) {
+            for pixel_x in x..(x + width).min(image.width()) {
+                let [red, green, blue] = match self {
+                    GridBackground::Color(color) => color.0,
+                    GridBackground::Checkerboard => {
+                        let is_dark =
+                            ((pixel_x - x) / CHECKER_SIZE + (pixel_y - y) / CHECKER_SIZE) % 2 == 1;
+                        if is_dark {
+                            [204, 204, 204]
+                        } else {
+                            [255, 255, 255]
+                        }
+                    }
+                };
+                image.put_pixel(pixel_x, pixel_y, Rgba([red, green, blue, 255]));
+            }
+        }
+    }
+}
+
+impl FromStr for GridBackground {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "checkerboard" {
+            Ok(GridBackground::Checkerboard)
+        } else {
+            Ok(GridBackground::Color(s.parse()?))
+        }
+    }
+}
+
+impl TryFrom<String> for GridBackground {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for GridBackground {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridBackground::Checkerboard => write!(f, "checkerboard"),
+            GridBackground::Color(color) => write!(f, "{}", color),
+        }
+    }
+}
+
+impl From<GridBackground> for String {
+    fn from(background: GridBackground) -> Self {
+        background.to_string()
+    }
+}
+
+/// Writes `summary.html`, a page with no external file that shows every image of the sweep in the grids of the summary images
+/// Clicking an image shows it bigger, with a slider to compare it with `source` and the command that makes only this image
+pub(crate) fn generate_html_summary(
+    base_image_path: impl AsRef<Path>,
+    source: &PhotonImage,
+    params: &LineartParams,
+    input_dir: impl AsRef<Path>,
+) -> Result<()> {
+    info!(
+        "Starting generation of the HTML summary for {:?}",
+        input_dir.as_ref()
+    );
+    let sweep = &params.sweep;
+    let rows = &sweep.axes()[sweep.rows()];
+    let columns = &sweep.axes()[sweep.columns()];
+    let title = base_image_path
+        .as_ref()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut source_png = vec![];
+    pipeline::from_photon(source.clone())
+        .write_to(&mut Cursor::new(&mut source_png), ImageFormat::Png)?;
+
+    let mut html = String::new();
+    write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape(&title),
+        STYLE,
+        escape(&title)
+    )?;
+    let blocks = sweep.blocks();
+    let block_count = blocks.map_or(1, |block_axis| sweep.axes()[block_axis].values().len());
+    for facet in sweep.facets() {
+        let facet_name = sweep.summary_name(&facet);
+        write!(html, "<section>\n<h2>{}</h2>\n", escape(&facet_name))?;
+        if params.summary.include_source {
+            writeln!(
+                html,
+                "<figure><img class=\"reference\" src=\"data:image/png;base64,{}\"><figcaption>Source</figcaption></figure>",
+                base64(&source_png)
+            )?;
+        }
+        html.push_str("<div class=\"blocks\">\n");
+        for block_index in 0..block_count {
+            html.push_str("<table>\n");
+            if let Some(block_axis) = blocks {
+                let axis = &sweep.axes()[block_axis];
+                write!(
+                    html,
+                    "<caption>{}: {}</caption>",
+                    escape(&axis_title(axis.name())),
+                    escape(&axis.values()[block_index].to_string())
+                )?;
+            }
+            write!(
+                html,
+                "<tr><th>{} \\ {}</th>",
+                escape(&axis_title(rows.name())),
+                escape(&axis_title(columns.name()))
+            )?;
+            for value in columns.values() {
+                write!(html, "<th>{}</th>", escape(&value.to_string()))?;
+            }
+            html.push_str("</tr>\n");
+            for (row_index, row_value) in rows.values().iter().enumerate() {
+                write!(html, "<tr><th>{}</th>", escape(&row_value.to_string()))?;
+                for column_index in 0..columns.values().len() {
+                    let mut indices = facet.clone();
+                    indices[sweep.rows()] = row_index;
+                    indices[sweep.columns()] = column_index;
+                    if let Some(block_axis) = blocks {
+                        indices[block_axis] = block_index;
+                    }
+                    write_variant(
+                        &mut html,
+                        base_image_path.as_ref(),
+                        params,
+                        &indices,
+                        &input_dir,
+                    )?;
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</div>\n</section>\n");
+    }
+    write!(
+        html,
+        "<div id=\"viewer\" hidden>\n<div class=\"compare\"><img class=\"source\" src=\"data:image/png;base64,{}\"><img class=\"lineart\"></div>\n\
+         <input class=\"slider\" type=\"range\" min=\"0\" max=\"100\" value=\"0\" title=\"Source on the left, lineart on the right\">\n\
+         <p class=\"name\"></p>\n<p><code class=\"command\"></code> <button class=\"copy\">Copy</button></p>\n</div>\n<script>{}</script>\n</body>\n</html>\n",
+        base64(&source_png),
+        SCRIPT
+    )?;
+
+    let mut html_path = input_dir.as_ref().to_owned();
+    html_path.push("summary.html");
+    fs::write(&html_path, html)?;
+    info!(
+        "Finished generation of the HTML summary for {:?}",
+        input_dir.as_ref()
+    );
+    Ok(())
+}
+
+/// The shell command that generates only the image of a combination of the sweep, empty when `params` has no command
+pub(crate) fn variant_command(
+    params: &LineartParams,
+    base_image_path: &Path,
+    indices: &[usize],
+) -> String {
+    let mut command = params.summary.command.clone();
+    if !command.is_empty() {
+        command.push(String::from("--input-image"));
+        command.push(base_image_path.to_string_lossy().into_owned());
+        for (axis, &index) in params.sweep.axes().iter().zip(indices) {
+            command.push(String::from("--sweep"));
+            command.push(format!("{}={}", axis.name(), axis.values()[index]));
+        }
+    }
+    command
+        .iter()
+        .map(|argument| shell_quote(argument))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes the cell of the image of a combination of the sweep
+fn write_variant(
+    html: &mut String,
+    base_image_path: &Path,
+    params: &LineartParams,
+    indices: &[usize],
+    input_dir: impl AsRef<Path>,
+) -> Result<()> {
+    let sweep = &params.sweep;
+    let name = sweep.variant_name(indices);
+    let path = build_image_output_path(&input_dir, &name)?;
+    let png = fs::read(&path).with_context(|| format!("Cannot read {}", path))?;
+    let command = variant_command(params, base_image_path, indices);
+    write!(
+        html,
+        "<td><img class=\"variant\" src=\"data:image/png;base64,{}\" data-name=\"{}\" data-command=\"{}\" title=\"{}\"></td>",
+        base64(&png),
+        escape(&name),
+        escape(&command),
+        escape(&name)
+    )?;
+    Ok(())
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 1em; }
+table { border-collapse: collapse; }
+.blocks { display: flex; gap: 2em; align-items: flex-start; }
+caption { padding: 0.3em; font-weight: bold; }
+figure { margin: 0 0 1em 0; }
+img.reference { width: 12em; box-shadow: 0 0 0.2em #aaa; }
+th { padding: 0.3em; font-weight: normal; color: #555; }
+td { padding: 0.3em; }
+img.variant { width: 12em; cursor: zoom-in; background: white; box-shadow: 0 0 0.2em #aaa; }
+#viewer { position: fixed; inset: 0; background: rgba(0, 0, 0, 0.85); color: white; text-align: center; overflow: auto; padding: 1em; }
+#viewer[hidden] { display: none; }
+.compare { position: relative; display: inline-block; cursor: zoom-out; }
+.compare img { display: block; max-width: 90vw; max-height: 75vh; }
+.compare .lineart { position: absolute; top: 0; left: 0; width: 100%; height: 100%; background: white; }
+.slider { width: 50%; }
+code { user-select: all; }
+";
+
+const SCRIPT: &str = "
+const viewer = document.getElementById('viewer');
+const lineart = viewer.querySelector('.lineart');
+const slider = viewer.querySelector('.slider');
+const command = viewer.querySelector('.command');
+function reveal() {
+    lineart.style.clipPath = 'inset(0 0 0 ' + slider.value + '%)';
+}
+document.querySelectorAll('img.variant').forEach(function (image) {
+    image.addEventListener('click', function () {
+        lineart.src = image.src;
+        viewer.querySelector('.name').textContent = image.dataset.name;
+        command.textContent = image.dataset.command;
+        command.parentElement.hidden = image.dataset.command === '';
+        slider.value = 0;
+        reveal();
+        viewer.hidden = false;
+    });
+});
+slider.addEventListener('input', reveal);
+viewer.querySelector('.compare').addEventListener('click', function () {
+    viewer.hidden = true;
+});
+viewer.querySelector('.copy').addEventListener('click', function () {
+    navigator.clipboard.writeText(command.textContent);
+});
+document.addEventListener('keydown', function (event) {
+    if (event.key === 'Escape') {
+        viewer.hidden = true;
+    }
+});
+";
+
+/// Escapes the text to put it in HTML, both in the content of an element and in an attribute between double quotes
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Quotes an argument for a POSIX shell when it has other characters than the safe ones
+fn shell_quote(argument: &str) -> String {
+    let is_safe =
+        |character: char| character.is_ascii_alphanumeric() || "-_./=:,+@%".contains(character);
+    if !argument.is_empty() && argument.chars().all(is_safe) {
+        argument.to_owned()
+    } else {
+        format!("'{}'", argument.replace('\'', "'\\''"))
+    }
+}
+
+/// The standard base64 of the bytes, with padding, to put the images in the page
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,486 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    method::{LineartMethod, MethodRegistry},
+    params::LineartParams,
+};
+
+/// The axis of the blur radius given to the method
+pub const BLUR: &str = "blur";
+/// The axis of the darken level of the lines
+pub const DARKEN: &str = "darken";
+/// The axis of the strength of the pre-filter
+pub const PREFILTER: &str = "prefilter";
+/// The axis of the method
+pub const METHOD: &str = "method";
+
+/// The most values an axis can have, like the counts of the blur radii and darken levels
+const MAX_AXIS_VALUES: usize = u8::MAX as usize;
+
+/// The parameters that can be swept apart from [`BLUR`], [`DARKEN`], [`PREFILTER`] and [`METHOD`],
+/// named like their command line options, the parameters of the methods can also be swept by their name
+pub const SWEEPABLE_PARAMETERS: [&str; 20] = [
+    "canny_low_threshold",
+    "canny_high_threshold",
+    "xdog_k",
+    "xdog_sharpening",
+    "xdog_epsilon",
+    "xdog_phi",
+    "fdog_rho",
+    "fdog_sigma_m",
+    "fdog_tau",
+    "etf_radius",
+    "etf_iterations",
+    "bilateral_sigma_color",
+    "diffusion_kappa",
+    "transparency_threshold",
+    "opacity_threshold",
+    "despeckle_area",
+    "despeckle_length",
+    "fill_holes_area",
+    "trace_tolerance",
+    "plotter_mm_per_pixel",
+];
+
+/// The unit of the values of a swept parameter, shown in the titles of the summaries
+/// The parameters with no unit, like the factors of the methods, have none
+pub fn unit(name: &str) -> Option<&'static str> {
+    match name {
+        BLUR | "etf_radius" | "despeckle_length" | "trace_tolerance" => Some("px"),
+        "despeckle_area" | "fill_holes_area" => Some("px²"),
+        "bilateral_sigma_color"
+        | "diffusion_kappa"
+        | "transparency_threshold"
+        | "opacity_threshold" => Some("/255"),
+        "plotter_mm_per_pixel" => Some("mm"),
+        _ => None,
+    }
+}
+
+/// A value of an axis of the sweep
+#[derive(Clone, Debug)]
+pub enum SweepValue {
+    Number(f64),
+    Method(Arc<dyn LineartMethod>),
+}
+
+impl SweepValue {
+    fn number(&self, name: &str) -> Result<f64> {
+        match self {
+            SweepValue::Number(number) => Ok(*number),
+            SweepValue::Method(method) => {
+                bail!(
+                    "{} must be a number, got the method {}",
+                    name,
+                    method.name()
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for SweepValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepValue::Number(number) => write!(f, "{}", number),
+            SweepValue::Method(method) => write!(f, "{}", method.name()),
+        }
+    }
+}
+
+/// A parameter and the values it takes in the sweep, every combination of the values of the axes gives an image
+#[derive(Clone, Debug)]
+pub struct SweepAxis {
+    name: String,
+    values: Vec<SweepValue>,
+}
+
+impl SweepAxis {
+    /// The name is the one of a command line option, with underscores, see [`SWEEPABLE_PARAMETERS`]
+    pub fn new(name: &str, values: Vec<SweepValue>) -> Result<Self> {
+        let name = name.trim().replace('-', "_");
+        if name.is_empty() {
+            bail!("The swept parameter has no name");
+        }
+        if values.is_empty() || values.len() > MAX_AXIS_VALUES {
+            bail!(
+                "{} must have between 1 and {} values, got {}",
+                name,
+                MAX_AXIS_VALUES,
+                values.len()
+            );
+        }
+        Ok(SweepAxis { name, values })
+    }
+
+    /// The values from `start` to `end` included, every `step`
+    /// They are rounded to the thousandth, so that `0.1 * 3` gives `0.3` in the file names
+    pub fn range(name: &str, start: f64, end: f64, step: f64) -> Result<Self> {
+        if !start.is_finite() || !end.is_finite() || !step.is_finite() || step <= 0_f64 {
+            bail!(
+                "The range of {} must go from a number to a bigger one with a positive step, got {}..{}:{}",
+                name,
+                start,
+                end,
+                step
+            );
+        }
+        // the small margin keeps the end when the step doesn't add up exactly to it, like 0.1 * 3
+        let count = ((end - start) / step + 1e-9).floor() + 1_f64;
+        if count < 1_f64 || count > MAX_AXIS_VALUES as f64 {
+            bail!(
+                "The range of {} must have between 1 and {} values, got {}..{}:{}",
+                name,
+                MAX_AXIS_VALUES,
+                start,
+                end,
+                step
+            );
+        }
+        let values = (0..count as usize)
+            .map(|index| {
+                let value = start + index as f64 * step;
+                SweepValue::Number((value * 1000_f64).round() / 1000_f64)
+            })
+            .collect();
+        SweepAxis::new(name, values)
+    }
+
+    /// Reads an axis written `name=start..end:step` (`:step` defaults to 1) or `name=value,value,...`,
+    /// the values of the method axis are names of methods of the registry
+    pub fn parse(text: &str, registry: &MethodRegistry) -> Result<Self> {
+        let (name, values) = text
+            .split_once('=')
+            .with_context(|| format!("Expected name=values for the sweep, got {}", text))?;
+        let name = name.trim().replace('-', "_");
+        if name == METHOD {
+            let methods = values
+                .split(',')
+                .map(|method| {
+                    registry
+                        .get(method.trim())
+                        .map(SweepValue::Method)
+                        .with_context(|| {
+                            format!(
+                                "Unknown method {}, possible values: {}",
+                                method,
+                                registry.names().join(", ")
+                            )
+                        })
+                })
+                .collect::<Result<_>>()?;
+            return SweepAxis::new(&name, methods);
+        }
+        let number = |value: &str| {
+            value
+                .trim()
+                .parse::<f64>()
+                .with_context(|| format!("Invalid value {} for {}", value, name))
+        };
+        match values.split_once("..") {
+            Some((start, end)) => {
+                let (end, step) = end.split_once(':').unwrap_or((end, "1"));
+                SweepAxis::range(&name, number(start)?, number(end)?, number(step)?)
+            }
+            None => {
+                let numbers = values
+                    .split(',')
+                    .map(|value| number(value).map(SweepValue::Number))
+                    .collect::<Result<_>>()?;
+                SweepAxis::new(&name, numbers)
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn values(&self) -> &[SweepValue] {
+        &self.values
+    }
+}
+
+/// The axes of the sweep and the two of them drawn as the rows and the columns of the summary images
+/// The other axes are faceted: there is one summary image for each combination of their values,
+/// except the methods which are drawn side by side in the same summary images
+#[derive(Clone, Debug)]
+pub struct SweepSpec {
+    axes: Vec<SweepAxis>,
+    rows: usize,
+    columns: usize,
+}
+
+impl SweepSpec {
+    pub(crate) fn new(axes: Vec<SweepAxis>, rows: &str, columns: &str) -> Result<Self> {
+        for (index, axis) in axes.iter().enumerate() {
+            if axes[..index].iter().any(|other| other.name == axis.name) {
+                bail!("{} is swept more than once", axis.name);
+            }
+        }
+        let find = |name: &str| {
+            let name = name.trim().replace('-', "_");
+            axes.iter()
+                .position(|axis| axis.name == name)
+                .with_context(|| format!("The summary cannot use {}, it is not swept", name))
+        };
+        let rows = find(rows)?;
+        let columns = find(columns)?;
+        if rows == columns {
+            bail!("The rows and the columns of the summary must be different axes");
+        }
+        Ok(SweepSpec {
+            axes,
+            rows,
+            columns,
+        })
+    }
+
+    pub fn axes(&self) -> &[SweepAxis] {
+        &self.axes
+    }
+
+    pub fn axis_index(&self, name: &str) -> Option<usize> {
+        self.axes.iter().position(|axis| axis.name == name)
+    }
+
+    /// The index of the axis drawn as the rows of the summary images
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The index of the axis drawn as the columns of the summary images
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// The index of the axis of the methods when it is neither the rows nor the columns,
+    /// each method is then drawn as its own block of columns in the summary images so that they can be compared
+    pub fn blocks(&self) -> Option<usize> {
+        self.axis_index(METHOD)
+            .filter(|&axis| axis != self.rows && axis != self.columns)
+    }
+
+    /// Every combination of the indices of the values of the axes, the last axis changes first
+    pub fn combinations(&self) -> Vec<Vec<usize>> {
+        let mut combinations = vec![vec![]];
+        for axis in &self.axes {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination: Vec<usize>| {
+                    (0..axis.values.len()).map(move |index| {
+                        let mut combination = combination.clone();
+                        combination.push(index);
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+
+    /// The combinations of the faceted axes, with the indices of the rows, the columns and the blocks set to 0
+    pub fn facets(&self) -> Vec<Vec<usize>> {
+        let mut facets: Vec<Vec<usize>> = self
+            .combinations()
+            .into_iter()
+            .map(|mut combination| {
+                combination[self.rows] = 0;
+                combination[self.columns] = 0;
+                if let Some(blocks) = self.blocks() {
+                    combination[blocks] = 0;
+                }
+                combination
+            })
+            .collect();
+        facets.sort_unstable();
+        facets.dedup();
+        facets
+    }
+
+    /// The name of the image of a combination, every axis followed by its value like `blur_3_darken_2`
+    pub fn variant_name(&self, indices: &[usize]) -> String {
+        self.labels(indices, 0..self.axes.len())
+    }
+
+    /// The name of the summary image of a facet, `summary` followed by the faceted axes and their values
+    pub fn summary_name(&self, facet: &[usize]) -> String {
+        let blocks = self.blocks();
+        let faceted = (0..self.axes.len())
+            .filter(|&axis| axis != self.rows && axis != self.columns && Some(axis) != blocks);
+        let labels = self.labels(facet, faceted);
+        if labels.is_empty() {
+            String::from("summary")
+        } else {
+            format!("summary_{}", labels)
+        }
+    }
+
+    fn labels(&self, indices: &[usize], axes: impl Iterator<Item = usize>) -> String {
+        axes.map(|axis| {
+            format!(
+                "{}_{}",
+                self.axes[axis].name, self.axes[axis].values[indices[axis]]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+    }
+}
+
+/// One image of the sweep, with the values of the axes applied to the parameters
+#[derive(Clone, Debug)]
+pub struct Variant {
+    /// The index of the value of each axis of the sweep
+    pub indices: Vec<usize>,
+    /// The name of the image, see [`SweepSpec::variant_name`]
+    pub name: String,
+    pub prefilter_strength: u32,
+    pub blur_radius: i32,
+    pub darken: f32,
+    pub params: LineartParams,
+}
+
+impl Variant {
+    /// The variant of `params` for the values at `indices` on the axes of its sweep
+    pub(crate) fn new(params: &LineartParams, indices: Vec<usize>) -> Result<Self> {
+        let mut variant = Variant {
+            name: params.sweep.variant_name(&indices),
+            indices: indices.clone(),
+            prefilter_strength: 0,
+            blur_radius: 0,
+            darken: 0_f32,
+            params: params.clone(),
+        };
+        for (axis, &index) in params.sweep.axes.iter().zip(indices.iter()) {
+            variant
+                .apply(&axis.name, &axis.values[index])
+                .with_context(|| {
+                    format!("Invalid value {} for {}", axis.values[index], axis.name)
+                })?;
+        }
+        Ok(variant)
+    }
+
+    fn apply(&mut self, name: &str, value: &SweepValue) -> Result<()> {
+        let number = value.number(name);
+        let params = &mut self.params;
+        let method = &mut params.method_parameters;
+        match name {
+            BLUR => self.blur_radius = integer(number?)?,
+            DARKEN => self.darken = number? as f32,
+            PREFILTER => self.prefilter_strength = integer(number?)?,
+            METHOD => match value {
+                SweepValue::Method(swept_method) => params.method = swept_method.clone(),
+                SweepValue::Number(number) => bail!("Expected a method, got {}", number),
+            },
+            "canny_low_threshold" => method.canny_thresholds.0 = number? as f32,
+            "canny_high_threshold" => method.canny_thresholds.1 = number? as f32,
+            "xdog_k" => method.xdog.k = number? as f32,
+            "xdog_sharpening" => method.xdog.sharpening = number? as f32,
+            "xdog_epsilon" => method.xdog.epsilon = number? as f32,
+            "xdog_phi" => method.xdog.phi = number? as f32,
+            "fdog_rho" => method.fdog.rho = number? as f32,
+            "fdog_sigma_m" => method.fdog.sigma_m = number? as f32,
+            "fdog_tau" => method.fdog.tau = number? as f32,
+            "etf_radius" => method.fdog.etf_radius = integer(number?)?,
+            "etf_iterations" => method.fdog.etf_iterations = integer(number?)?,
+            "bilateral_sigma_color" | "diffusion_kappa" => {
+                let Some(prefilter) = &mut params.prefilter else {
+                    bail!("{} can only be swept with a pre-filter", name);
+                };
+                if name == "bilateral_sigma_color" {
+                    prefilter.bilateral_sigma_color = number? as f32;
+                } else {
+                    prefilter.diffusion_kappa = number? as f32;
+                }
+            }
+            "transparency_threshold" => params.alpha.transparency_threshold = integer(number?)?,
+            "opacity_threshold" => params.alpha.opacity_threshold = integer(number?)?,
+            "despeckle_area" => params.despeckle.min_area = integer(number?)?,
+            "despeckle_length" => params.despeckle.min_length = number? as f32,
+            "fill_holes_area" => params.despeckle.max_hole_area = integer(number?)?,
+            "trace_tolerance" => params.export_parameters.tolerance = number?,
+            "plotter_mm_per_pixel" => params.export_parameters.millimeters_per_pixel = number?,
+            // the other names are the parameters of the methods, checked against the methods of the sweep
+            _ => {
+                method.custom.insert(name.to_owned(), number?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Converts a swept number to a whole number of the type of the parameter
+fn integer<T: TryFrom<i64>>(number: f64) -> Result<T> {
+    if number.fract() != 0_f64 {
+        bail!("Expected a whole number, got {}", number);
+    }
+    T::try_from(number as i64)
+        .ok()
+        .with_context(|| format!("{} is out of range", number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(axis: &SweepAxis) -> Vec<f64> {
+        axis.values()
+            .iter()
+            .map(|value| value.number(axis.name()).unwrap())
+            .collect()
+    }
+
+    fn parse(text: &str) -> Result<SweepAxis> {
+        SweepAxis::parse(text, &MethodRegistry::builtin())
+    }
+
+    #[test]
+    fn range_with_fractional_step_keeps_its_end() {
+        let axis = parse("darken=0..0.3:0.1").unwrap();
+        assert_eq!(axis.name(), DARKEN);
+        assert_eq!(numbers(&axis), [0.0, 0.1, 0.2, 0.3]);
+    }
+
+    #[test]
+    fn range_with_negative_start() {
+        let axis = parse("xdog-epsilon=-0.2..0.2:0.2").unwrap();
+        assert_eq!(axis.name(), "xdog_epsilon");
+        assert_eq!(numbers(&axis), [-0.2, 0.0, 0.2]);
+        assert_eq!(
+            numbers(&parse("blur=-2..1").unwrap()),
+            [-2.0, -1.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn list_keeps_its_order() {
+        let axis = parse("blur=5, 1,3").unwrap();
+        assert_eq!(numbers(&axis), [5.0, 1.0, 3.0]);
+    }
+
+    #[test]
+    fn invalid_axes_are_rejected() {
+        assert!(parse("blur").is_err());
+        assert!(parse("blur=1,a").is_err());
+        assert!(parse("blur=3..1").is_err());
+        assert!(parse("blur=1..3:0").is_err());
+        assert!(parse("method=gaussian,unknown").is_err());
+    }
+
+    #[test]
+    fn float_is_rejected_for_integer_axis() {
+        let build = |text: &str| {
+            LineartParams::builder()
+                .sweep_axis(parse(text).unwrap())
+                .build()
+        };
+        assert!(build("blur=1,2").is_ok());
+        assert!(build("blur=1,1.5").is_err());
+        assert!(build("etf_radius=2.5").is_err());
+        assert!(build("xdog_k=0").is_err());
+    }
+}
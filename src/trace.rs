@@ -0,0 +1,417 @@
+use std::collections::BTreeSet;
+
+use image::{GrayImage, Luma};
+use imageproc::{geometry::approximate_polygon_dp, point::Point};
+use photon_rs::PhotonImage;
+
+/// The darkness (between 0 and 255) above which a pixel is considered to be ink
+const INK_THRESHOLD: u32 = 128;
+
+/// The cosine of the smallest turn angle (60°) for which a vertex of the simplified contour is kept as a sharp corner
+const CORNER_COSINE: f64 = 0.5;
+
+/// Maximum number of Newton-Raphson reparameterizations before splitting a curve in two
+const MAX_REPARAMETERIZATIONS: usize = 4;
+
+type Vector = (f64, f64);
+
+/// A closed path made of cubic Bezier curves, all the curves are joined end to end
+pub(crate) struct BezierPath {
+    start: Vector,
+    /// The two control points and the end point of each curve
+    curves: Vec<[Vector; 3]>,
+}
+
+impl BezierPath {
+    /// The path in the SVG path data syntax
+    pub(crate) fn to_svg_path_data(&self) -> String {
+        let mut data = format!("M{:.2} {:.2}", self.start.0, self.start.1);
+        for [control_1, control_2, end] in &self.curves {
+            data.push_str(&format!(
+                " C{:.2} {:.2} {:.2} {:.2} {:.2} {:.2}",
+                control_1.0, control_1.1, control_2.0, control_2.1, end.0, end.1
+            ));
+        }
+        data.push_str(" Z");
+        data
+    }
+}
+
+/// Binary image where the ink is 255 and the background is 0
+/// A pixel is ink when it is dark and opaque enough, so it works for both transparent and opaque linearts
+pub(crate) fn binarize(image: &PhotonImage) -> GrayImage {
+    let raw_pixels = image.get_raw_pixels();
+    let mut binary = GrayImage::new(image.get_width(), image.get_height());
+    for (pixel, rgba) in binary.pixels_mut().zip(raw_pixels.chunks_exact(4)) {
+        let luminance = (rgba[0] as u32 + rgba[1] as u32 + rgba[2] as u32) / 3;
+        let darkness = (255 - luminance) * rgba[3] as u32 / 255;
+        if darkness >= INK_THRESHOLD {
+            *pixel = Luma([255]);
+        }
+    }
+    binary
+}
+
+/// Traces the outlines of the ink of a binary image into closed Bezier paths, both the outer borders and the holes
+/// `tolerance` is the maximum distance in pixels between the curves and the traced pixels, a higher tolerance gives fewer and smoother curves
+pub(crate) fn trace_outlines(binary: &GrayImage, tolerance: f64) -> Vec<BezierPath> {
+    trace_boundaries(binary)
+        .iter()
+        .map(|boundary| fit_closed_path(boundary, tolerance))
+        .collect()
+}
+
+/// Follows the edges between the ink and the background pixels, the points are on the corners of the pixels
+/// so that even one pixel wide lines have an area, as in potrace
+/// Each boundary keeps the ink on its right, so the outer borders are clockwise and the holes are counterclockwise
+/// When two ink pixels only touch by a corner, they are considered connected
+fn trace_boundaries(binary: &GrayImage) -> Vec<Vec<Point<i32>>> {
+    let width = binary.width() as i32;
+    let height = binary.height() as i32;
+    let is_ink = |x: i32, y: i32| {
+        x >= 0 && y >= 0 && x < width && y < height && binary.get_pixel(x as u32, y as u32).0[0] > 0
+    };
+
+    // each edge is a starting corner and a unit direction
+    let mut all_edges = BTreeSet::new();
+    for y in 0..height {
+        for x in 0..width {
+            if !is_ink(x, y) {
+                continue;
+            }
+            if !is_ink(x, y - 1) {
+                all_edges.insert(((x, y), (1, 0)));
+            }
+            if !is_ink(x + 1, y) {
+                all_edges.insert(((x + 1, y), (0, 1)));
+            }
+            if !is_ink(x, y + 1) {
+                all_edges.insert(((x + 1, y + 1), (-1, 0)));
+            }
+            if !is_ink(x - 1, y) {
+                all_edges.insert(((x, y + 1), (0, -1)));
+            }
+        }
+    }
+
+    let mut remaining_edges = all_edges.clone();
+    let mut boundaries = vec![];
+    while let Some((start, first_direction)) = remaining_edges.pop_first() {
+        let mut boundary = vec![Point::new(start.0, start.1)];
+        let mut corner = (start.0 + first_direction.0, start.1 + first_direction.1);
+        let mut direction = first_direction;
+        loop {
+            // turning left first keeps the pixels touching by a corner in the same boundary
+            let left = (direction.1, -direction.0);
+            let right = (-direction.1, direction.0);
+            let Some(next_direction) = [left, direction, right]
+                .into_iter()
+                .find(|candidate| all_edges.contains(&(corner, *candidate)))
+            else {
+                break;
+            };
+            if corner == start && next_direction == first_direction {
+                break;
+            }
+            remaining_edges.remove(&(corner, next_direction));
+            boundary.push(Point::new(corner.0, corner.1));
+            corner = (corner.0 + next_direction.0, corner.1 + next_direction.1);
+            direction = next_direction;
+        }
+        boundaries.push(boundary);
+    }
+    boundaries
+}
+
+fn fit_closed_path(contour: &[Point<i32>], tolerance: f64) -> BezierPath {
+    let corners = find_corners(contour, tolerance);
+    // the staircase of the pixel corners is smoothed, except on the sharp corners which must stay in place
+    let points: Vec<Vector> = (0..contour.len())
+        .map(|i| {
+            let as_vector = |point: &Point<i32>| (point.x as f64, point.y as f64);
+            let current = as_vector(&contour[i]);
+            if corners.contains(&i) {
+                return current;
+            }
+            let previous = as_vector(&contour[(i + contour.len() - 1) % contour.len()]);
+            let next = as_vector(&contour[(i + 1) % contour.len()]);
+            add(scale(current, 0.5), scale(add(previous, next), 0.25))
+        })
+        .collect();
+    // a closed curve without corners is cut in two halves, so that each half has distinct ends
+    let cuts = if corners.len() < 2 {
+        let first = corners.first().copied().unwrap_or(0);
+        vec![first, (first + points.len() / 2) % points.len()]
+    } else {
+        corners
+    };
+
+    let mut curves = vec![];
+    for (i, &cut_start) in cuts.iter().enumerate() {
+        let cut_end = cuts[(i + 1) % cuts.len()];
+        // the section between two cuts, wrapping around the end of the contour, both cuts included
+        let mut section = vec![points[cut_start]];
+        let mut index = cut_start;
+        while index != cut_end {
+            index = (index + 1) % points.len();
+            section.push(points[index]);
+        }
+        let first_tangent = normalize(subtract(section[1], section[0]));
+        let last_tangent = normalize(subtract(
+            section[section.len() - 2],
+            section[section.len() - 1],
+        ));
+        fit_cubic(
+            &section,
+            first_tangent,
+            last_tangent,
+            tolerance * tolerance,
+            &mut curves,
+        );
+    }
+    BezierPath {
+        start: points[cuts[0]],
+        curves,
+    }
+}
+
+/// The indices in the contour of the vertices where the direction changes sharply
+fn find_corners(contour: &[Point<i32>], tolerance: f64) -> Vec<usize> {
+    let polygon = approximate_polygon_dp(contour, tolerance.max(1_f64), true);
+    if polygon.len() < 3 {
+        return vec![];
+    }
+    let mut corners = vec![];
+    let mut search_start = 0;
+    for (i, vertex) in polygon.iter().enumerate() {
+        let previous = polygon[(i + polygon.len() - 1) % polygon.len()];
+        let next = polygon[(i + 1) % polygon.len()];
+        let incoming = normalize((
+            (vertex.x - previous.x) as f64,
+            (vertex.y - previous.y) as f64,
+        ));
+        let outgoing = normalize(((next.x - vertex.x) as f64, (next.y - vertex.y) as f64));
+        if dot(incoming, outgoing) >= CORNER_COSINE {
+            continue;
+        }
+        // the simplified polygon keeps the order of the contour, so the search can continue from the last corner
+        if let Some(offset) = contour[search_start..]
+            .iter()
+            .position(|point| point == vertex)
+        {
+            corners.push(search_start + offset);
+            search_start += offset + 1;
+        }
+    }
+    corners
+}
+
+/// Fits cubic Bezier curves to the points, splitting them until the squared error is below `max_error`
+/// This is the algorithm of Philip J. Schneider, "An Algorithm for Automatically Fitting Digitized Curves"
+fn fit_cubic(
+    points: &[Vector],
+    first_tangent: Vector,
+    last_tangent: Vector,
+    max_error: f64,
+    curves: &mut Vec<[Vector; 3]>,
+) {
+    let first = points[0];
+    let last = points[points.len() - 1];
+    if points.len() == 2 {
+        let distance = length(subtract(last, first)) / 3_f64;
+        curves.push([
+            add(first, scale(first_tangent, distance)),
+            add(last, scale(last_tangent, distance)),
+            last,
+        ]);
+        return;
+    }
+
+    let mut parameters = chord_length_parameters(points);
+    let mut curve = generate_bezier(points, &parameters, first_tangent, last_tangent);
+    let (mut error, mut split_index) = max_squared_error(points, &curve, &parameters);
+    if error < max_error {
+        curves.push([curve[1], curve[2], curve[3]]);
+        return;
+    }
+
+    // when the error is not too big, a better parameterization can be enough
+    if error < max_error * 4_f64 {
+        for _ in 0..MAX_REPARAMETERIZATIONS {
+            parameters = reparameterize(points, &parameters, &curve);
+            curve = generate_bezier(points, &parameters, first_tangent, last_tangent);
+            (error, split_index) = max_squared_error(points, &curve, &parameters);
+            if error < max_error {
+                curves.push([curve[1], curve[2], curve[3]]);
+                return;
+            }
+        }
+    }
+
+    let split_index = split_index.clamp(1, points.len() - 2);
+    let center_tangent = normalize(subtract(points[split_index - 1], points[split_index + 1]));
+    fit_cubic(
+        &points[..=split_index],
+        first_tangent,
+        center_tangent,
+        max_error,
+        curves,
+    );
+    fit_cubic(
+        &points[split_index..],
+        scale(center_tangent, -1_f64),
+        last_tangent,
+        max_error,
+        curves,
+    );
+}
+
+/// Least squares fit of the length of the two tangents of the curve, the ends and tangent directions being fixed
+fn generate_bezier(
+    points: &[Vector],
+    parameters: &[f64],
+    first_tangent: Vector,
+    last_tangent: Vector,
+) -> [Vector; 4] {
+    let first = points[0];
+    let last = points[points.len() - 1];
+    let mut c = [[0_f64; 2]; 2];
+    let mut x = [0_f64; 2];
+    for (point, &u) in points.iter().zip(parameters) {
+        let a_1 = scale(first_tangent, 3_f64 * u * (1_f64 - u) * (1_f64 - u));
+        let a_2 = scale(last_tangent, 3_f64 * u * u * (1_f64 - u));
+        c[0][0] += dot(a_1, a_1);
+        c[0][1] += dot(a_1, a_2);
+        c[1][1] += dot(a_2, a_2);
+        let on_line = bezier_point(&[first, first, last, last], u);
+        let difference = subtract(*point, on_line);
+        x[0] += dot(a_1, difference);
+        x[1] += dot(a_2, difference);
+    }
+    c[1][0] = c[0][1];
+
+    let determinant = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let (alpha_1, alpha_2) = if determinant.abs() > f64::EPSILON {
+        (
+            (x[0] * c[1][1] - x[1] * c[0][1]) / determinant,
+            (c[0][0] * x[1] - c[1][0] * x[0]) / determinant,
+        )
+    } else {
+        (0_f64, 0_f64)
+    };
+
+    // the fit can give degenerate or reversed tangents, a third of the distance is a safe fallback
+    let segment_length = length(subtract(last, first));
+    let epsilon = 1e-6 * segment_length;
+    let (alpha_1, alpha_2) = if alpha_1 < epsilon || alpha_2 < epsilon {
+        (segment_length / 3_f64, segment_length / 3_f64)
+    } else {
+        (alpha_1, alpha_2)
+    };
+    [
+        first,
+        add(first, scale(first_tangent, alpha_1)),
+        add(last, scale(last_tangent, alpha_2)),
+        last,
+    ]
+}
+
+/// Improves the parameter of each point with one Newton-Raphson step towards the closest point of the curve
+fn reparameterize(points: &[Vector], parameters: &[f64], curve: &[Vector; 4]) -> Vec<f64> {
+    let first_derivative: [Vector; 3] =
+        std::array::from_fn(|i| scale(subtract(curve[i + 1], curve[i]), 3_f64));
+    let second_derivative: [Vector; 2] = std::array::from_fn(|i| {
+        scale(
+            subtract(first_derivative[i + 1], first_derivative[i]),
+            2_f64,
+        )
+    });
+    points
+        .iter()
+        .zip(parameters)
+        .map(|(point, &u)| {
+            let difference = subtract(bezier_point(curve, u), *point);
+            let derivative = bezier_point(&first_derivative, u);
+            let derivative_2 = bezier_point(&second_derivative, u);
+            let numerator = dot(difference, derivative);
+            let denominator = dot(derivative, derivative) + dot(difference, derivative_2);
+            if denominator.abs() < f64::EPSILON {
+                u
+            } else {
+                (u - numerator / denominator).clamp(0_f64, 1_f64)
+            }
+        })
+        .collect()
+}
+
+/// The largest squared distance between the points and the curve, and the index of the point where it happens
+fn max_squared_error(points: &[Vector], curve: &[Vector; 4], parameters: &[f64]) -> (f64, usize) {
+    let mut max_error = 0_f64;
+    let mut split_index = points.len() / 2;
+    for (i, (point, &u)) in points.iter().zip(parameters).enumerate() {
+        let difference = subtract(bezier_point(curve, u), *point);
+        let error = dot(difference, difference);
+        if error >= max_error {
+            max_error = error;
+            split_index = i;
+        }
+    }
+    (max_error, split_index)
+}
+
+/// The parameter of each point is its distance along the polyline, divided by the total length
+fn chord_length_parameters(points: &[Vector]) -> Vec<f64> {
+    let mut parameters = Vec::with_capacity(points.len());
+    parameters.push(0_f64);
+    for window in points.windows(2) {
+        let previous = *parameters.last().unwrap_or(&0_f64);
+        parameters.push(previous + length(subtract(window[1], window[0])));
+    }
+    let total = *parameters.last().unwrap_or(&0_f64);
+    if total > 0_f64 {
+        for parameter in parameters.iter_mut() {
+            *parameter /= total;
+        }
+    }
+    parameters
+}
+
+/// A point of a Bezier curve of any degree, with De Casteljau's algorithm
+fn bezier_point(control_points: &[Vector], u: f64) -> Vector {
+    let mut points = control_points.to_vec();
+    for degree in (1..points.len()).rev() {
+        for i in 0..degree {
+            points[i] = add(scale(points[i], 1_f64 - u), scale(points[i + 1], u));
+        }
+    }
+    points[0]
+}
+
+fn add(a: Vector, b: Vector) -> Vector {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn subtract(a: Vector, b: Vector) -> Vector {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(a: Vector, factor: f64) -> Vector {
+    (a.0 * factor, a.1 * factor)
+}
+
+fn dot(a: Vector, b: Vector) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn length(a: Vector) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: Vector) -> Vector {
+    let norm = length(a);
+    if norm > 0_f64 {
+        scale(a, 1_f64 / norm)
+    } else {
+        a
+    }
+}
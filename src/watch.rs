@@ -0,0 +1,126 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{error, info, warn};
+use notify::{
+    event::{AccessKind, AccessMode},
+    EventKind, RecursiveMode, Watcher,
+};
+
+use crate::{
+    image_generation,
+    inputs::{self, InputFilter, InputImage},
+    manifest::{DirectoryManifest, InputManifest},
+    params::LineartParams,
+};
+
+/// How often the changed images are checked to see if they are quiet for long enough
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Generates the linearts of every image of the directory, then generates them again each time an image is added or changed, until the process is stopped
+/// The images are only generated once they have not changed for `debounce`, so that the images that are still being written or copied are not read,
+/// and the images whose outputs are up to date are skipped, see [`generate_images_and_grid_in`](image_generation::generate_images_and_grid_in)
+/// The images are watched with inotify on Linux, and with the native events of the other systems
+pub fn watch(
+    directory: impl AsRef<Path>,
+    filter: &InputFilter,
+    params: &LineartParams,
+    output_dir: impl AsRef<Path>,
+    debounce: Duration,
+) -> Result<()> {
+    // the paths of the events are built from the watched path, the same path is given to `find_images` so that they can be compared
+    let directory = fs::canonicalize(directory.as_ref())
+        .with_context(|| format!("Cannot watch {:?}", directory.as_ref()))?;
+    let output_dir = output_dir.as_ref();
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let mode = if filter.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher.watch(&directory, mode)?;
+
+    // the manifest of the directory keeps the last run of every image
+    let mut manifests: BTreeMap<PathBuf, InputManifest> = BTreeMap::new();
+    let mut generate = |input_images: &[InputImage]| {
+        for input_manifest in image_generation::generate_inputs(input_images, params) {
+            if input_manifest.error.is_none() {
+                info!(
+                    "Finished {:?} in {:.1}s",
+                    input_manifest.source,
+                    input_manifest.milliseconds as f32 / 1000_f32
+                );
+            }
+            manifests.insert(input_manifest.source.clone(), input_manifest);
+        }
+        let manifest = DirectoryManifest {
+            input_directory: directory.clone(),
+            inputs: manifests.values().cloned().collect(),
+        };
+        if let Err(e) = manifest.write(output_dir) {
+            error!("Cannot write the manifest of {:?}: {:?}", output_dir, e);
+        }
+    };
+    generate(&inputs::find_images(&directory, filter, output_dir)?);
+    info!("Watching {:?} for new or changed images", directory);
+
+    let mut changed: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                let is_write = matches!(
+                    event.kind,
+                    EventKind::Create(_)
+                        | EventKind::Modify(_)
+                        | EventKind::Access(AccessKind::Close(AccessMode::Write))
+                );
+                if is_write {
+                    for path in event.paths {
+                        changed.insert(path, Instant::now());
+                    }
+                }
+            }
+            Ok(Err(e)) => warn!("Error while watching {:?}: {:?}", directory, e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => bail!("Stopped watching {:?}", directory),
+        }
+
+        let now = Instant::now();
+        let quiet: Vec<PathBuf> = changed
+            .iter()
+            .filter(|(_, &last_change)| now.duration_since(last_change) >= debounce)
+            .map(|(path, _)| path.clone())
+            .collect();
+        if quiet.is_empty() {
+            continue;
+        }
+        for path in &quiet {
+            changed.remove(path);
+        }
+        // the images are found again to apply the filter and to give each one the same output directory as in a full run,
+        // the changes of the other files and of the outputs are ignored
+        let found = match inputs::find_images(&directory, filter, output_dir) {
+            Ok(found) => found,
+            // a subdirectory can be renamed or deleted between the event and the scan, the next changes are still watched
+            Err(e) => {
+                error!("Cannot find the images of {:?}: {:?}", directory, e);
+                continue;
+            }
+        };
+        let input_images: Vec<InputImage> = found
+            .into_iter()
+            .filter(|input_image| quiet.contains(&input_image.path))
+            .collect();
+        for input_image in &input_images {
+            info!("{:?} changed, generating its linearts", input_image.path);
+        }
+        generate(&input_images);
+    }
+}
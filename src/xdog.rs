@@ -0,0 +1,78 @@
+use anyhow::{bail, Result};
+use image::{GrayImage, ImageBuffer, Luma};
+use imageproc::filter::gaussian_blur_f32;
+
+/// The parameters of the Extended Difference of Gaussians, they don't depend on the blur radius
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct XdogParameters {
+    /// Ratio between the standard deviations of the two Gaussians, usually 1.6
+    pub k: f32,
+    /// Sharpening factor p, the higher it is the stronger the edges are emphasized
+    pub sharpening: f32,
+    /// Soft threshold epsilon, values of the sharpened image above it become white
+    pub epsilon: f32,
+    /// Steepness phi of the soft threshold, the higher it is the closer the result is to a binary image
+    pub phi: f32,
+}
+
+impl Default for XdogParameters {
+    fn default() -> Self {
+        XdogParameters {
+            k: 1.6,
+            sharpening: 20.0,
+            epsilon: 0.1,
+            phi: 10.0,
+        }
+    }
+}
+
+/// Runs the Extended Difference of Gaussians on a greyscale image
+/// The image is sharpened with `(1 + p) * G(sigma) - p * G(k * sigma)` then passed through a soft threshold:
+/// values above epsilon become white and the others fall off with `1 + tanh(phi * (value - epsilon))`
+/// The returned image has black lines on a white background
+pub(crate) fn xdog(image: &GrayImage, sigma: f32, parameters: XdogParameters) -> Result<GrayImage> {
+    check_parameters(&parameters)?;
+    let intensity: ImageBuffer<Luma<f32>, Vec<f32>> =
+        ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+            Luma([image.get_pixel(x, y).0[0] as f32 / 255_f32])
+        });
+    let small_blur = gaussian_blur_f32(&intensity, sigma);
+    let large_blur = gaussian_blur_f32(&intensity, sigma * parameters.k);
+
+    let mut lines = GrayImage::new(image.width(), image.height());
+    for (x, y, pixel) in lines.enumerate_pixels_mut() {
+        let small = small_blur.get_pixel(x, y).0[0];
+        let large = large_blur.get_pixel(x, y).0[0];
+        let sharpened = (1_f32 + parameters.sharpening) * small - parameters.sharpening * large;
+        let value = if sharpened >= parameters.epsilon {
+            1_f32
+        } else {
+            1_f32 + (parameters.phi * (sharpened - parameters.epsilon)).tanh()
+        };
+        *pixel = Luma([(value.clamp(0_f32, 1_f32) * 255_f32).round() as u8]);
+    }
+    Ok(lines)
+}
+
+/// Checks the parameters, `k` must be positive since it multiplies the standard deviation of the largest Gaussian
+/// and a negative `phi` would invert the lines
+pub(crate) fn check_parameters(parameters: &XdogParameters) -> Result<()> {
+    if !parameters.k.is_finite() || parameters.k <= 0_f32 {
+        bail!("The XDoG k must be a positive number, got {}", parameters.k);
+    }
+    for (name, value) in [
+        ("sharpening", parameters.sharpening),
+        ("epsilon", parameters.epsilon),
+    ] {
+        if !value.is_finite() {
+            bail!("The XDoG {} must be a number, got {}", name, value);
+        }
+    }
+    if !parameters.phi.is_finite() || parameters.phi < 0_f32 {
+        bail!(
+            "The XDoG phi must be a positive number, got {}",
+            parameters.phi
+        );
+    }
+    Ok(())
+}